@@ -1,9 +1,12 @@
-use super::DataSource;
+use super::{BatchIter, COLUMN_BATCH_SIZE, DataSource, search_batches};
+use crate::draw::RenderSettings;
+use crate::search::{Needle, Progress, SearchJob};
 use arrow::csv::ReaderBuilder;
 use arrow::csv::reader::Format;
 use arrow::datatypes::{DataType, Field, Schema, SchemaBuilder};
 use arrow::record_batch::RecordBatch;
 use fileslice::FileSlice;
+use std::borrow::Cow;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::sync::Arc;
@@ -14,19 +17,56 @@ pub struct CsvFile {
     fs: FileSlice,
     /// The nth row begins at byte `row_offsets[n]` in `fs`
     row_offsets: Vec<u64>,
+    /// Bytes before this have already been scanned for row boundaries
+    scan_pos: u64,
+    /// The state of the scanner at `scan_pos`
+    scan_state: ScanState,
+    delimiter: u8,
     format: Format,
     schema: Arc<Schema>,
+    settings: RenderSettings,
+}
+
+/// Where we are in the current record.  We need to know this in order to tell
+/// whether a newline is the end of a record or part of a quoted field.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+enum ScanState {
+    #[default]
+    FieldStart,
+    Unquoted,
+    Quoted,
+    /// We've seen a quote inside a quoted field.  This is either the closing
+    /// quote or the first half of an escaped quote.
+    QuotedQuote,
+}
+
+impl ScanState {
+    /// Returns the next state, and whether `byte` was the end of a record
+    fn next(self, byte: u8, delimiter: u8) -> (ScanState, bool) {
+        match (self, byte) {
+            (ScanState::Quoted, b'"') => (ScanState::QuotedQuote, false),
+            (ScanState::Quoted, _) => (ScanState::Quoted, false),
+            (ScanState::FieldStart | ScanState::QuotedQuote, b'"') => (ScanState::Quoted, false),
+            (_, b'\n') => (ScanState::FieldStart, true),
+            (_, x) if x == delimiter => (ScanState::FieldStart, false),
+            (_, _) => (ScanState::Unquoted, false),
+        }
+    }
 }
 
 impl CsvFile {
-    pub fn new(file: File, delimiter: u8) -> anyhow::Result<CsvFile> {
+    pub fn new(file: File, delimiter: u8, settings: &RenderSettings) -> anyhow::Result<CsvFile> {
         Ok(CsvFile {
             fs: FileSlice::new(file.try_clone()?).slice(0..0),
             format: Format::default()
                 .with_header(false)
                 .with_delimiter(delimiter),
             row_offsets: vec![],
+            scan_pos: 0,
+            scan_state: ScanState::default(),
+            delimiter,
             schema: Schema::empty().into(),
+            settings: settings.clone(),
         })
     }
 
@@ -46,25 +86,28 @@ impl CsvFile {
     }
 
     // TODO: Optimize (memchr + mmap?)
-    // FIXME: Not all newlines are new rows in CSV
     fn add_new_lines(&mut self) -> anyhow::Result<usize> {
         let n_rows_then = self.row_count();
-        let mut line_start = self.row_offsets.last().copied().unwrap_or(0);
-
-        let mut new_bytes = BufReader::new(self.fs.slice(line_start..));
+        let mut new_bytes = BufReader::new(self.fs.slice(self.scan_pos..));
         let start = Instant::now();
-        let mut line = Vec::new();
 
         loop {
-            new_bytes.read_until(b'\n', &mut line)?;
-
-            // If we reached EOF rather than a newline, ensure we don't record that as a row offset
-            if line.last().is_none_or(|b| *b != b'\n') {
+            let buf = new_bytes.fill_buf()?;
+            // If we reached EOF in the middle of a row, we'll pick up from
+            // here next time
+            if buf.is_empty() {
                 break;
             }
-            line_start += line.len() as u64;
-            self.row_offsets.push(line_start);
-            line.clear();
+            for (i, byte) in buf.iter().enumerate() {
+                let (state, end_of_row) = self.scan_state.next(*byte, self.delimiter);
+                self.scan_state = state;
+                if end_of_row {
+                    self.row_offsets.push(self.scan_pos + i as u64 + 1);
+                }
+            }
+            let n = buf.len();
+            new_bytes.consume(n);
+            self.scan_pos += n as u64;
 
             if start.elapsed() > Duration::from_millis(10) {
                 break;
@@ -110,10 +153,14 @@ impl DataSource for CsvFile {
         let n_bytes_then = self.fs.end_pos();
         self.fs.expand();
         let n_bytes_now = self.fs.end_pos();
-        if n_bytes_now == n_bytes_then {
+        // Even if the file hasn't grown, the last scan may have run out of
+        // time before reaching the end
+        if n_bytes_now == n_bytes_then && self.scan_pos == n_bytes_now {
             return Ok(0);
         }
-        debug!("File size has changed! ({n_bytes_then} -> {n_bytes_now})");
+        if n_bytes_now != n_bytes_then {
+            debug!("File size has changed! ({n_bytes_then} -> {n_bytes_now})");
+        }
 
         if self.schema.fields().is_empty() {
             match self.read_header() {
@@ -172,22 +219,125 @@ impl DataSource for CsvFile {
         Ok(batch)
     }
 
-    // FIXME: Not all newlines are new rows in CSV
-    fn search(&self, needle: &str) -> anyhow::Result<Vec<usize>> {
-        let mut matches = vec![];
-        for (row, txt) in BufReader::new(self.fs.clone()).lines().skip(1).enumerate() {
-            let txt = txt?;
-            if txt.contains(needle) {
-                matches.push(row);
+    fn search(&self, needle: &Needle) -> anyhow::Result<SearchJob> {
+        if let Some(column) = needle.column() {
+            let batches = self.column_batches(&[column])?;
+            return Ok(search_batches(batches, needle, &self.settings));
+        }
+        let Some((start, end)) = self.row_offsets.first().zip(self.row_offsets.last()) else {
+            return Ok(Box::new(|_| Ok(())));
+        };
+        let rdr = BufReader::new(self.fs.slice(*start..*end));
+        let delimiter = self.delimiter;
+        let needle = needle.clone();
+        Ok(Box::new(move |progress| {
+            search(rdr, delimiter, &needle, progress)
+        }))
+    }
+
+    /// We read the columns as strings, since the schema we've inferred so far
+    /// might not hold for the rest of the file
+    fn column_batches(&self, columns: &[&str]) -> anyhow::Result<BatchIter> {
+        let Some((start, end)) = self.row_offsets.first().zip(self.row_offsets.last()) else {
+            return Ok(Box::new(std::iter::empty()));
+        };
+        let idxs = columns
+            .iter()
+            .map(|x| self.schema.index_of(x))
+            .collect::<Result<Vec<_>, _>>()?;
+        let schema = Schema::new(
+            self.schema
+                .fields()
+                .iter()
+                .map(|f| Field::new(f.name(), DataType::Utf8, true))
+                .collect::<Vec<_>>(),
+        );
+        let rdr = ReaderBuilder::new(schema.into())
+            .with_format(self.format.clone())
+            .with_projection(idxs)
+            .with_batch_size(COLUMN_BATCH_SIZE)
+            .build(self.fs.slice(*start..*end))?;
+        Ok(Box::new(rdr.map(|x| Ok(x?))))
+    }
+}
+
+/// Matches the needle against each field separately, so that eg. `^` anchors
+/// to the start of a field, like it does for the other backends
+fn search(
+    mut rdr: impl BufRead,
+    delimiter: u8,
+    needle: &Needle,
+    progress: &mut Progress,
+) -> anyhow::Result<()> {
+    let mut scan_state = ScanState::default();
+    let mut row = 0;
+    let mut row_matched = false;
+    let mut field = vec![];
+    loop {
+        let buf = rdr.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(());
+        }
+        for byte in buf {
+            let (state, end_of_row) = scan_state.next(*byte, delimiter);
+            scan_state = state;
+            if state != ScanState::FieldStart {
+                field.push(*byte);
+                continue;
+            }
+            // We've reached the end of a field
+            if !row_matched && needle.is_match(&unquote(&field)) {
+                row_matched = true;
+            }
+            field.clear();
+            if end_of_row {
+                if row_matched {
+                    progress.push(row);
+                }
+                row_matched = false;
+                row += 1;
             }
         }
-        Ok(matches)
+        let n = buf.len();
+        rdr.consume(n);
+        if !progress.update(row) {
+            return Ok(());
+        }
+    }
+}
+
+/// Strips the line ending and quotes from a raw field
+fn unquote(field: &[u8]) -> Cow<'_, [u8]> {
+    let field = field.strip_suffix(b"\r").unwrap_or(field);
+    match field
+        .strip_prefix(b"\"")
+        .and_then(|x| x.strip_suffix(b"\""))
+    {
+        Some(x) if memchr::memchr(b'"', x).is_some() => {
+            // Escaped quotes are doubled
+            let mut out = Vec::with_capacity(x.len());
+            let mut prev_quote = false;
+            for b in x {
+                if prev_quote && *b == b'"' {
+                    prev_quote = false;
+                    continue;
+                }
+                prev_quote = *b == b'"';
+                out.push(*b);
+            }
+            Cow::Owned(out)
+        }
+        Some(x) => Cow::Borrowed(x),
+        None => Cow::Borrowed(field),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::prompt::Dir;
+    use crate::search::{Search, SearchOpts};
+    use arrow::array::AsArray;
     use std::io::Write;
 
     // This tests the situation in which `tass` catches up with stdin, running out of bytes
@@ -206,7 +356,7 @@ a,b,c,d
         let mut tmp = tempfile::NamedTempFile::new()?;
         tmp.write_all(&data.as_bytes()[..halfway_through_codepoint])?;
 
-        let mut source = CsvFile::new(File::open(tmp.path())?, b',')?;
+        let mut source = CsvFile::new(File::open(tmp.path())?, b',', &RenderSettings::default())?;
         source.check_for_new_rows()?;
 
         tmp.write_all(&data.as_bytes()[halfway_through_codepoint..])?;
@@ -216,4 +366,92 @@ a,b,c,d
 
         Ok(())
     }
+
+    // Newlines inside quoted fields aren't row boundaries, even if we run out
+    // of bytes in the middle of the field.
+    #[test]
+    fn handles_quoted_newlines() -> anyhow::Result<()> {
+        let data = "\
+a,b
+1,\"two
+lines\"
+2,\"escaped \"\"quote\"\"
+and newline\"
+3,x\"y
+";
+
+        let halfway_through_field = data.find("lines").unwrap();
+
+        let mut tmp = tempfile::NamedTempFile::new()?;
+        tmp.write_all(&data.as_bytes()[..halfway_through_field])?;
+
+        let mut source = CsvFile::new(File::open(tmp.path())?, b',', &RenderSettings::default())?;
+        source.check_for_new_rows()?;
+        assert_eq!(source.row_offsets, [4]);
+
+        tmp.write_all(&data.as_bytes()[halfway_through_field..])?;
+        source.check_for_new_rows()?;
+        assert_eq!(source.row_offsets, [4, 18, 52, 58]);
+
+        let batch = source.fetch_batch(0, 3)?;
+        let col = batch.column(1).as_string::<i32>();
+        assert_eq!(col.value(0), "two\nlines");
+        assert_eq!(col.value(1), "escaped \"quote\"\nand newline");
+        assert_eq!(col.value(2), "x\"y");
+
+        let search = |needle: Needle| -> anyhow::Result<Vec<usize>> {
+            let job = source.search(&needle)?;
+            let mut search = Search::spawn(job, needle, Dir::Forward, source.row_count(), None);
+            while search.is_running() {
+                std::thread::sleep(Duration::from_millis(1));
+                search.poll();
+            }
+            Ok(std::mem::take(&mut search.matches))
+        };
+        let literal = |x| Needle::new(x, SearchOpts::default()).unwrap();
+        let regex = |x| {
+            let opts = SearchOpts {
+                regex: true,
+                ..SearchOpts::default()
+            };
+            Needle::new(x, opts).unwrap()
+        };
+        assert_eq!(search(literal("lines"))?, [0]);
+        assert_eq!(search(literal("newline"))?, [1]);
+        // Regexes are matched against one (unquoted) field at a time
+        assert_eq!(search(regex("^x"))?, [2]);
+        assert!(search(regex("^lines"))?.is_empty());
+        assert_eq!(search(regex("\"quote\"\n"))?, [1]);
+        // Column-scoped searches only look at the parsed values in that column
+        let in_a = |x| literal(x).in_column(Some("a".into()));
+        let in_b = |x| regex(x).in_column(Some("b".into()));
+        assert!(search(in_a("escaped"))?.is_empty());
+        assert_eq!(search(in_a("2"))?, [1]);
+        assert_eq!(search(in_b("^x\"y$"))?, [2]);
+
+        Ok(())
+    }
+
+    // If a scan runs out of time in the middle of a row, the next check picks
+    // up where it left off, even though the file hasn't grown.
+    #[test]
+    fn resumes_long_scans() -> anyhow::Result<()> {
+        let mut tmp = tempfile::NamedTempFile::new()?;
+        write!(tmp, "a,b\n1,\"")?;
+        // Far more than can be scanned in one go
+        tmp.write_all(&"x\n".repeat(16 << 20).into_bytes())?;
+        write!(tmp, "\"\n2,y\n")?;
+
+        let mut source = CsvFile::new(File::open(tmp.path())?, b',', &RenderSettings::default())?;
+        source.check_for_new_rows()?;
+        assert_eq!(source.row_count(), 0);
+        for _ in 0..10_000 {
+            if source.row_count() == 2 {
+                break;
+            }
+            source.check_for_new_rows()?;
+        }
+        assert_eq!(source.row_count(), 2);
+        Ok(())
+    }
 }
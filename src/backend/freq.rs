@@ -0,0 +1,223 @@
+use super::{BatchIter, DataSource, search_batch};
+use crate::draw::RenderSettings;
+use crate::search::{Needle, SearchJob};
+use arrow::array::{ArrayRef, Float64Array, UInt64Array};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+use arrow::row::{OwnedRow, RowConverter, SortField};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// How often each value of a column appears, most frequent first.  The
+/// columns are `value`, `count`, and `percent`.
+pub struct FreqTable {
+    batch: RecordBatch,
+    settings: RenderSettings,
+}
+
+/// A frequency table which is being built on a background thread
+pub struct FreqJob {
+    pub column: String,
+    rows_counted: usize,
+    total_rows: usize,
+    result: Option<anyhow::Result<FreqTable>>,
+    rx: Receiver<Msg>,
+}
+
+enum Msg {
+    Progress(usize),
+    Done(anyhow::Result<FreqTable>),
+}
+
+impl FreqJob {
+    /// `values` should yield every value of the column, with type `dtype`.
+    /// Nulls are counted too.  The job stops when the `FreqJob` is dropped.
+    pub fn spawn(
+        column: String,
+        dtype: DataType,
+        values: impl Iterator<Item = anyhow::Result<ArrayRef>> + Send + 'static,
+        total_rows: usize,
+        settings: RenderSettings,
+    ) -> FreqJob {
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let start = Instant::now();
+            let mut last_sent = Instant::now();
+            let result = (|| {
+                let mut counter = Counter::new(&dtype)?;
+                for col in values {
+                    counter.add(col?)?;
+                    if last_sent.elapsed() > Duration::from_millis(200) {
+                        if tx.send(Msg::Progress(counter.total)).is_err() {
+                            // The UI thread has lost interest
+                            return Ok(None);
+                        }
+                        last_sent = Instant::now();
+                    }
+                }
+                Ok(Some(counter.finish(&settings)?))
+            })();
+            debug!(took = ?start.elapsed(), "Frequency table finished");
+            if let Some(result) = result.transpose() {
+                let _ = tx.send(Msg::Done(result));
+            }
+        });
+        FreqJob {
+            column,
+            rows_counted: 0,
+            total_rows,
+            result: None,
+            rx,
+        }
+    }
+
+    /// Collects any new results from the background thread.  Returns true if
+    /// anything changed.
+    pub fn poll(&mut self) -> bool {
+        let mut changed = false;
+        loop {
+            match self.rx.try_recv() {
+                Ok(Msg::Progress(n)) => self.rows_counted = n,
+                Ok(Msg::Done(result)) => self.result = Some(result),
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => return changed,
+            }
+            changed = true;
+        }
+    }
+
+    /// The finished table, once the job is done
+    pub fn take_result(&mut self) -> Option<anyhow::Result<FreqTable>> {
+        self.result.take()
+    }
+
+    /// A short description of the job's progress for the footer
+    pub fn status(&self) -> String {
+        let pct = self.rows_counted * 100 / self.total_rows.max(1);
+        format!("(counting {}, {pct}%)", self.column)
+    }
+}
+
+/// Counts the values of a column, a chunk at a time
+struct Counter {
+    dtype: DataType,
+    converter: RowConverter,
+    counts: HashMap<OwnedRow, usize>,
+    total: usize,
+}
+
+impl Counter {
+    fn new(dtype: &DataType) -> anyhow::Result<Counter> {
+        Ok(Counter {
+            dtype: dtype.clone(),
+            converter: RowConverter::new(vec![SortField::new(dtype.clone())])?,
+            counts: HashMap::new(),
+            total: 0,
+        })
+    }
+
+    fn add(&mut self, col: ArrayRef) -> anyhow::Result<()> {
+        self.total += col.len();
+        for row in self.converter.convert_columns(&[col])?.iter() {
+            *self.counts.entry(row.owned()).or_default() += 1;
+        }
+        Ok(())
+    }
+
+    fn finish(self, settings: &RenderSettings) -> anyhow::Result<FreqTable> {
+        let Counter {
+            dtype,
+            converter,
+            counts,
+            total,
+        } = self;
+        debug!("Found {} distinct values in {total} rows", counts.len());
+
+        let mut counts = counts.into_iter().collect::<Vec<_>>();
+        // Ties are broken by value
+        counts.sort_unstable_by(|(x1, n1), (x2, n2)| n2.cmp(n1).then_with(|| x1.cmp(x2)));
+        let value = converter
+            .convert_rows(counts.iter().map(|(x, _)| x.row()))?
+            .remove(0);
+        let count = UInt64Array::from_iter_values(counts.iter().map(|(_, n)| *n as u64));
+        let percent = Float64Array::from_iter_values(
+            counts.iter().map(|(_, n)| *n as f64 * 100.0 / total as f64),
+        );
+        let schema = Schema::new(vec![
+            Field::new("value", dtype, true),
+            Field::new("count", DataType::UInt64, false),
+            Field::new("percent", DataType::Float64, false),
+        ]);
+        let batch = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![value, Arc::new(count), Arc::new(percent)],
+        )?;
+        Ok(FreqTable {
+            batch,
+            settings: settings.clone(),
+        })
+    }
+}
+
+impl DataSource for FreqTable {
+    fn check_for_new_rows(&mut self) -> anyhow::Result<usize> {
+        Ok(0)
+    }
+
+    fn row_count(&self) -> usize {
+        self.batch.num_rows()
+    }
+
+    fn fetch_batch(&mut self, offset: usize, len: usize) -> anyhow::Result<RecordBatch> {
+        let offset = offset.min(self.batch.num_rows());
+        let len = len.min(self.batch.num_rows() - offset);
+        Ok(self.batch.slice(offset, len))
+    }
+
+    fn search(&self, needle: &Needle) -> anyhow::Result<SearchJob> {
+        let batch = self.batch.clone();
+        let needle = needle.clone();
+        let settings = self.settings.clone();
+        Ok(Box::new(move |progress| {
+            search_batch(&batch, &needle, &settings, 0, progress)?;
+            progress.update(batch.num_rows());
+            Ok(())
+        }))
+    }
+
+    fn column_batches(&self, columns: &[&str]) -> anyhow::Result<BatchIter> {
+        let schema = self.batch.schema();
+        let idxs = columns
+            .iter()
+            .map(|x| schema.index_of(x))
+            .collect::<Result<Vec<_>, _>>()?;
+        let batch = self.batch.project(&idxs)?;
+        Ok(Box::new(std::iter::once(Ok(batch))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, AsArray, StringArray};
+    use arrow::datatypes::UInt64Type;
+
+    #[test]
+    fn counts_values() -> anyhow::Result<()> {
+        let chunks = [vec![Some("b"), None, Some("a")], vec![Some("b"), Some("a")]];
+        let mut counter = Counter::new(&DataType::Utf8)?;
+        for chunk in chunks {
+            counter.add(Arc::new(StringArray::from(chunk)))?;
+        }
+        let table = counter.finish(&RenderSettings::default())?;
+        let value = table.batch.column(0).as_string::<i32>();
+        let count = table.batch.column(1).as_primitive::<UInt64Type>();
+        let rows = (0..table.row_count())
+            .map(|i| (value.is_valid(i).then(|| value.value(i)), count.value(i)))
+            .collect::<Vec<_>>();
+        assert_eq!(rows, [(Some("a"), 2), (Some("b"), 2), (None, 1)]);
+        Ok(())
+    }
+}
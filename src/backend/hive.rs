@@ -0,0 +1,131 @@
+use anyhow::ensure;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// The value hive uses for null partition keys
+const NULL_PARTITION: &str = "__HIVE_DEFAULT_PARTITION__";
+
+/// A file in a partitioned dataset
+pub struct PartitionedFile {
+    pub path: PathBuf,
+    /// Taken from the `key=value` components of the path, outermost first.
+    /// Values are unescaped; hive's null placeholder becomes `None`.
+    pub keys: Vec<(String, Option<String>)>,
+}
+
+/// Finds all the parquet files under `dir`, in sorted order.  Hidden files and
+/// ones starting with an underscore (eg. `_SUCCESS`) are skipped.
+pub fn discover(dir: &Path) -> anyhow::Result<Vec<PartitionedFile>> {
+    let mut files = vec![];
+    walk(dir, &mut vec![], &mut files)?;
+    ensure!(!files.is_empty(), "No parquet files in {}", dir.display());
+    debug!("Found {} files in {}", files.len(), dir.display());
+    Ok(files)
+}
+
+fn walk(
+    dir: &Path,
+    keys: &mut Vec<(String, Option<String>)>,
+    out: &mut Vec<PartitionedFile>,
+) -> anyhow::Result<()> {
+    let mut entries = std::fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|x| x.file_name());
+    for entry in entries {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with(['.', '_']) {
+            continue;
+        }
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            let key = parse_partition(&name);
+            let pushed = key.is_some();
+            keys.extend(key);
+            walk(&path, keys, out)?;
+            if pushed {
+                keys.pop();
+            }
+        } else if path.extension().is_some_and(|x| x == "parquet") {
+            out.push(PartitionedFile {
+                path,
+                keys: keys.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Parses a directory name like "date=2026-10-01"
+fn parse_partition(name: &str) -> Option<(String, Option<String>)> {
+    let (key, value) = name.split_once('=')?;
+    let value = (value != NULL_PARTITION).then(|| unescape(value));
+    Some((unescape(key), value))
+}
+
+/// Undoes hive's percent-encoding of special characters
+fn unescape(x: &str) -> String {
+    let mut bytes = Vec::with_capacity(x.len());
+    let mut rest = x.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        let hex = tail
+            .get(..2)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (b, hex) {
+            (b'%', Some(decoded)) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            _ => {
+                bytes.push(b);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_partition_keys() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        for path in [
+            "date=2026-10-01/region=eu/a.parquet",
+            "date=2026-10-01/region=eu/_SUCCESS",
+            "date=2026-10-01/region=us%2Feast/b.parquet",
+            "date=__HIVE_DEFAULT_PARTITION__/c.parquet",
+            "date=2026-10-02/.d.parquet.crc",
+        ] {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            std::fs::write(path, "")?;
+        }
+        let files = discover(dir.path())?;
+        let keys = files
+            .iter()
+            .map(|x| {
+                let name = x.path.file_name().unwrap().to_str().unwrap();
+                let keys = x.keys.iter().map(|(k, v)| (k.as_str(), v.as_deref()));
+                (name, keys.collect::<Vec<_>>())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            [
+                (
+                    "a.parquet",
+                    vec![("date", Some("2026-10-01")), ("region", Some("eu"))]
+                ),
+                (
+                    "b.parquet",
+                    vec![("date", Some("2026-10-01")), ("region", Some("us/east"))]
+                ),
+                ("c.parquet", vec![("date", None)]),
+            ]
+        );
+        Ok(())
+    }
+}
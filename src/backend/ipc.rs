@@ -0,0 +1,411 @@
+use super::{BatchIter, DataSource, search_batches};
+use crate::draw::RenderSettings;
+use crate::search::{Needle, SearchJob};
+use anyhow::{anyhow, bail};
+use arrow::datatypes::Schema;
+use arrow::ipc::reader::{FileReader, StreamReader, read_footer_length};
+use arrow::ipc::{MessageHeader, root_as_footer, root_as_message};
+use arrow::record_batch::RecordBatch;
+use fileslice::FileSlice;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info};
+
+const CONTINUATION_MARKER: [u8; 4] = [0xff; 4];
+
+/// An Arrow IPC file (aka. Feather v2)
+pub struct IpcFile {
+    rdr: FileReader<BufReader<File>>,
+    /// The nth record batch begins at row `row_offsets[n]`
+    row_offsets: Vec<usize>,
+    settings: RenderSettings,
+}
+
+impl IpcFile {
+    pub fn new(file: File, settings: &RenderSettings) -> anyhow::Result<IpcFile> {
+        // We don't support live-updating IPC files (the footer is only written
+        // at the end), so we may as well cache the row counts
+        let row_offsets = count_rows(&file)?;
+        let rdr = FileReader::try_new_buffered(file, None)?;
+        Ok(IpcFile {
+            rdr,
+            row_offsets,
+            settings: settings.clone(),
+        })
+    }
+}
+
+impl DataSource for IpcFile {
+    fn check_for_new_rows(&mut self) -> anyhow::Result<usize> {
+        Ok(0)
+    }
+
+    fn row_count(&self) -> usize {
+        self.row_offsets.last().copied().unwrap_or(0)
+    }
+
+    fn fetch_batch(&mut self, offset: usize, len: usize) -> anyhow::Result<RecordBatch> {
+        debug!(offset, len, "Fetching a batch");
+        let batch_idxs = batches_containing(&self.row_offsets, offset..(offset + len));
+        if batch_idxs.is_empty() {
+            return Ok(RecordBatch::new_empty(self.rdr.schema()));
+        }
+        let first_row = self.row_offsets[batch_idxs.start];
+        self.rdr.set_index(batch_idxs.start)?;
+        let batches = (&mut self.rdr)
+            .take(batch_idxs.len())
+            .collect::<Result<Vec<_>, _>>()?;
+        let batch = arrow::compute::concat_batches(&self.rdr.schema(), &batches)?;
+        debug!(len = batch.num_rows(), "Loaded a record batch");
+        Ok(slice_batch(batch, offset - first_row, len))
+    }
+
+    fn search(&self, needle: &Needle) -> anyhow::Result<SearchJob> {
+        let batches = self.column_batches(&search_columns(&self.rdr.schema(), needle))?;
+        Ok(search_batches(batches, needle, &self.settings))
+    }
+
+    fn column_batches(&self, columns: &[&str]) -> anyhow::Result<BatchIter> {
+        let idxs = projection(&self.rdr.schema(), columns)?;
+        let file = self.rdr.get_ref().get_ref().try_clone()?;
+        let rdr = FileReader::try_new_buffered(file, Some(idxs))?;
+        Ok(Box::new(rdr.map(|x| Ok(x?))))
+    }
+}
+
+/// Reads the footer to find out how many rows are in each record batch
+fn count_rows(file: &File) -> anyhow::Result<Vec<usize>> {
+    let start = Instant::now();
+    let mut file = BufReader::new(file.try_clone()?);
+
+    let mut buf = [0; 10];
+    file.seek(SeekFrom::End(-10))?;
+    file.read_exact(&mut buf)?;
+    let footer_len = read_footer_length(buf)?;
+    let mut footer = vec![0; footer_len];
+    file.seek(SeekFrom::End(-10 - footer_len as i64))?;
+    file.read_exact(&mut footer)?;
+    let footer = root_as_footer(&footer).map_err(|e| anyhow!("Invalid IPC footer: {e}"))?;
+
+    let mut row_offsets = vec![0];
+    for block in footer.recordBatches().into_iter().flatten() {
+        let mut meta = vec![0; block.metaDataLength() as usize];
+        file.seek(SeekFrom::Start(block.offset() as u64))?;
+        file.read_exact(&mut meta)?;
+        let meta = match meta.get(..4) {
+            Some(x) if x == CONTINUATION_MARKER => &meta[8..],
+            _ => &meta[4..],
+        };
+        let msg = root_as_message(meta).map_err(|e| anyhow!("Invalid IPC message: {e}"))?;
+        let n_rows = msg
+            .header_as_record_batch()
+            .ok_or_else(|| anyhow!("Expected a record batch"))?
+            .length() as usize;
+        row_offsets.push(row_offsets.last().unwrap() + n_rows);
+    }
+    debug!(
+        "Counted {} rows (took {:?})",
+        row_offsets.last().unwrap(),
+        start.elapsed(),
+    );
+    Ok(row_offsets)
+}
+
+/// An Arrow IPC stream.  Unlike the file format, this can still be growing
+pub struct IpcStream {
+    fs: FileSlice,
+    schema: Arc<Schema>,
+    /// The bytes of the schema message
+    schema_msg: Range<u64>,
+    /// The bytes of each dictionary batch message.  These must be replayed
+    /// before the record batches which refer to them
+    dictionaries: Vec<Range<u64>>,
+    /// The nth record batch begins at byte `batch_offsets[n]` in `fs`
+    batch_offsets: Vec<u64>,
+    /// The nth record batch begins at row `row_offsets[n]`
+    row_offsets: Vec<usize>,
+    /// The byte at which the next (unread) message begins
+    next_msg: u64,
+    /// Whether we've seen the end-of-stream marker
+    finished: bool,
+    settings: RenderSettings,
+}
+
+impl IpcStream {
+    pub fn new(file: File, settings: &RenderSettings) -> anyhow::Result<IpcStream> {
+        Ok(IpcStream {
+            fs: FileSlice::new(file.try_clone()?).slice(0..0),
+            schema: Schema::empty().into(),
+            schema_msg: 0..0,
+            dictionaries: vec![],
+            batch_offsets: vec![],
+            row_offsets: vec![0],
+            next_msg: 0,
+            finished: false,
+            settings: settings.clone(),
+        })
+    }
+
+    fn read_new_messages(&mut self) -> anyhow::Result<usize> {
+        let n_rows_then = self.row_count();
+        let start = Instant::now();
+        while !self.finished {
+            let Some((msg, len)) = read_message(&self.fs, self.next_msg)? else {
+                break;
+            };
+            let bytes = self.next_msg..(self.next_msg + len);
+            match msg {
+                Message::Schema => {
+                    self.schema =
+                        StreamReader::try_new(self.fs.slice(bytes.clone()), None)?.schema();
+                    self.schema_msg = bytes.clone();
+                    for f in self.schema.fields() {
+                        info!("Read schema {}: {}", f.name(), f.data_type());
+                    }
+                }
+                Message::Dictionary => self.dictionaries.push(bytes.clone()),
+                Message::RecordBatch { n_rows } => {
+                    self.batch_offsets.push(bytes.start);
+                    self.row_offsets.push(self.row_count() + n_rows);
+                }
+                Message::EndOfStream => self.finished = true,
+            }
+            self.next_msg = bytes.end;
+
+            if start.elapsed() > Duration::from_millis(10) {
+                break;
+            }
+        }
+        Ok(self.row_count() - n_rows_then)
+    }
+}
+
+impl DataSource for IpcStream {
+    fn check_for_new_rows(&mut self) -> anyhow::Result<usize> {
+        let n_bytes_then = self.fs.end_pos();
+        self.fs.expand();
+        let n_bytes_now = self.fs.end_pos();
+        if n_bytes_now == n_bytes_then && self.next_msg == n_bytes_now {
+            return Ok(0);
+        }
+        debug!("File size has changed! ({n_bytes_then} -> {n_bytes_now})");
+
+        let n = self.read_new_messages()?;
+        debug!("Added {n} new rows");
+        if n == 0 {
+            error!("Caught up with the EOF");
+        }
+
+        Ok(n)
+    }
+
+    fn row_count(&self) -> usize {
+        self.row_offsets.last().copied().unwrap_or(0)
+    }
+
+    fn fetch_batch(&mut self, offset: usize, len: usize) -> anyhow::Result<RecordBatch> {
+        debug!(offset, len, "Fetching a batch");
+        let batch_idxs = batches_containing(&self.row_offsets, offset..(offset + len));
+        if batch_idxs.is_empty() {
+            return Ok(RecordBatch::new_empty(self.schema.clone()));
+        }
+        let first_row = self.row_offsets[batch_idxs.start];
+        let byte_start = self.batch_offsets[batch_idxs.start];
+        let byte_end = self
+            .batch_offsets
+            .get(batch_idxs.end)
+            .copied()
+            .unwrap_or(self.next_msg);
+        debug!(byte_start, byte_end, "Sliced the file");
+
+        // Construct a stream containing just the messages we need
+        let mut msgs: Box<dyn Read> = Box::new(self.fs.slice(self.schema_msg.clone()));
+        for dict in self.dictionaries.iter().filter(|x| x.end <= byte_start) {
+            msgs = Box::new(msgs.chain(self.fs.slice(dict.clone())));
+        }
+        msgs = Box::new(msgs.chain(self.fs.slice(byte_start..byte_end)));
+
+        let batches = StreamReader::try_new_buffered(msgs, None)?.collect::<Result<Vec<_>, _>>()?;
+        let batch = arrow::compute::concat_batches(&self.schema, &batches)?;
+        debug!(len = batch.num_rows(), "Loaded a record batch");
+        Ok(slice_batch(batch, offset - first_row, len))
+    }
+
+    fn search(&self, needle: &Needle) -> anyhow::Result<SearchJob> {
+        let batches = self.column_batches(&search_columns(&self.schema, needle))?;
+        Ok(search_batches(batches, needle, &self.settings))
+    }
+
+    fn column_batches(&self, columns: &[&str]) -> anyhow::Result<BatchIter> {
+        let idxs = projection(&self.schema, columns)?;
+        // Everything we've seen so far, in order.  The reader stops at the end
+        // of the slice if there's no end-of-stream marker.
+        let msgs = self.fs.slice(self.schema_msg.start..self.next_msg);
+        let rdr = StreamReader::try_new_buffered(msgs, Some(idxs))?;
+        Ok(Box::new(rdr.map(|x| Ok(x?))))
+    }
+}
+
+/// The indices of the columns, in schema order
+fn projection(schema: &Schema, columns: &[&str]) -> anyhow::Result<Vec<usize>> {
+    let mut idxs = columns
+        .iter()
+        .map(|x| schema.index_of(x))
+        .collect::<Result<Vec<_>, _>>()?;
+    idxs.sort_unstable();
+    Ok(idxs)
+}
+
+/// The columns which the needle should be matched against
+fn search_columns<'a>(schema: &'a Schema, needle: &'a Needle) -> Vec<&'a str> {
+    match needle.column() {
+        Some(column) => vec![column],
+        None => schema.fields().iter().map(|x| x.name().as_str()).collect(),
+    }
+}
+
+enum Message {
+    Schema,
+    Dictionary,
+    RecordBatch { n_rows: usize },
+    EndOfStream,
+}
+
+/// Reads the header of the message beginning at byte `pos` in `fs`.  Returns
+/// the kind of message and its total length in bytes, or `None` if the message
+/// hasn't been completely written yet.
+fn read_message(fs: &FileSlice, pos: u64) -> anyhow::Result<Option<(Message, u64)>> {
+    let available = fs.end_pos().saturating_sub(pos);
+    let mut rdr = fs.slice(pos..);
+
+    let mut prefix = [0; 4];
+    let mut prefix_len = 4;
+    if available < prefix_len {
+        return Ok(None);
+    }
+    rdr.read_exact(&mut prefix)?;
+    // Streams written before Arrow 0.15 don't have the continuation marker
+    if prefix == CONTINUATION_MARKER {
+        prefix_len += 4;
+        if available < prefix_len {
+            return Ok(None);
+        }
+        rdr.read_exact(&mut prefix)?;
+    }
+    let meta_len = u32::from_le_bytes(prefix) as u64;
+    if meta_len == 0 {
+        return Ok(Some((Message::EndOfStream, prefix_len)));
+    }
+    if available < prefix_len + meta_len {
+        return Ok(None);
+    }
+
+    let mut meta = vec![0; meta_len as usize];
+    rdr.read_exact(&mut meta)?;
+    let msg = root_as_message(&meta).map_err(|e| anyhow!("Invalid IPC message: {e}"))?;
+    let len = prefix_len + meta_len + msg.bodyLength() as u64;
+    if available < len {
+        return Ok(None);
+    }
+    let msg = match msg.header_type() {
+        MessageHeader::Schema => Message::Schema,
+        MessageHeader::DictionaryBatch => Message::Dictionary,
+        MessageHeader::RecordBatch => Message::RecordBatch {
+            n_rows: msg
+                .header_as_record_batch()
+                .ok_or_else(|| anyhow!("Expected a record batch"))?
+                .length() as usize,
+        },
+        x => bail!("Unsupported IPC message: {x:?}"),
+    };
+    Ok(Some((msg, len)))
+}
+
+/// The indices of the record batches which contain the given rows
+fn batches_containing(row_offsets: &[usize], rows: Range<usize>) -> Range<usize> {
+    let n_batches = row_offsets.len().saturating_sub(1);
+    let first = row_offsets
+        .partition_point(|x| *x <= rows.start)
+        .saturating_sub(1);
+    let last = row_offsets.partition_point(|x| *x < rows.end);
+    first.min(n_batches)..last.min(n_batches)
+}
+
+fn slice_batch(batch: RecordBatch, offset: usize, len: usize) -> RecordBatch {
+    let offset = offset.min(batch.num_rows());
+    let len = len.min(batch.num_rows() - offset);
+    batch.slice(offset, len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::run_job;
+    use arrow::array::{AsArray, Int32Array};
+    use arrow::datatypes::{DataType, Field, Int32Type};
+    use arrow::ipc::writer::StreamWriter;
+    use std::io::Write;
+
+    // This tests the situation in which `tass` catches up with the producer,
+    // running out of bytes in the middle of a record batch.
+    #[test]
+    fn handles_unfinished_batch() -> anyhow::Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let mut data = vec![];
+        let mut wtr = StreamWriter::try_new(&mut data, &schema)?;
+        for xs in [[1, 2, 3], [4, 5, 6], [7, 8, 9]] {
+            let col = Arc::new(Int32Array::from(xs.to_vec()));
+            wtr.write(&RecordBatch::try_new(schema.clone(), vec![col])?)?;
+        }
+        wtr.finish()?;
+
+        let halfway_through = data.len() / 2;
+
+        let mut tmp = tempfile::NamedTempFile::new()?;
+        tmp.write_all(&data[..halfway_through])?;
+
+        let mut source = IpcStream::new(File::open(tmp.path())?, &RenderSettings::default())?;
+        source.check_for_new_rows()?;
+        assert!(source.row_count() < 9);
+
+        tmp.write_all(&data[halfway_through..])?;
+        source.check_for_new_rows()?;
+        assert_eq!(source.row_offsets, [0, 3, 6, 9]);
+        assert!(source.finished);
+
+        let batch = source.fetch_batch(2, 5)?;
+        let col = batch.column(0).as_primitive::<Int32Type>();
+        assert_eq!(col.values(), &[3, 4, 5, 6, 7]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn searches_all_columns() -> anyhow::Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::Int32, false),
+            Field::new("b", DataType::Int32, false),
+        ]));
+        let mut tmp = tempfile::NamedTempFile::new()?;
+        let mut wtr = StreamWriter::try_new(&mut tmp, &schema)?;
+        for (xs, ys) in [([1, 2], [30, 20]), ([3, 4], [10, 40])] {
+            let cols = vec![
+                Arc::new(Int32Array::from(xs.to_vec())) as _,
+                Arc::new(Int32Array::from(ys.to_vec())) as _,
+            ];
+            wtr.write(&RecordBatch::try_new(schema.clone(), cols)?)?;
+        }
+        wtr.finish()?;
+
+        let mut source = IpcStream::new(File::open(tmp.path())?, &RenderSettings::default())?;
+        source.check_for_new_rows()?;
+        let needle = Needle::new("3", Default::default())?;
+        assert_eq!(run_job(source.search(&needle)?)?, [0, 2]);
+        let needle = needle.in_column(Some("a".into()));
+        assert_eq!(run_job(source.search(&needle)?)?, [2]);
+        Ok(())
+    }
+}
@@ -1,4 +1,6 @@
-use super::DataSource;
+use super::{BatchIter, COLUMN_BATCH_SIZE, DataSource, search_batches};
+use crate::draw::RenderSettings;
+use crate::search::{Needle, SearchJob};
 use arrow::datatypes::{DataType, Field, Schema, SchemaBuilder};
 use arrow::json::ReaderBuilder;
 use arrow::json::reader::infer_json_schema;
@@ -15,15 +17,17 @@ pub struct JsonFile {
     /// The nth row begins at byte `row_offsets[n]` in `fs`
     row_offsets: Vec<u64>,
     schema: Arc<Schema>,
+    settings: RenderSettings,
 }
 
 impl JsonFile {
-    pub fn new(file: File) -> anyhow::Result<JsonFile> {
+    pub fn new(file: File, settings: &RenderSettings) -> anyhow::Result<JsonFile> {
         warn!("JSON support is experimental");
         Ok(JsonFile {
             fs: FileSlice::new(file.try_clone()?).slice(0..0),
             row_offsets: vec![],
             schema: Schema::empty().into(),
+            settings: settings.clone(),
         })
     }
 
@@ -172,14 +176,30 @@ impl DataSource for JsonFile {
         Ok(batch)
     }
 
-    fn search(&self, needle: &str) -> anyhow::Result<Vec<usize>> {
-        let mut matches = vec![];
-        for (row, txt) in BufReader::new(self.fs.clone()).lines().enumerate() {
-            let txt = txt?;
-            if txt.contains(needle) {
-                matches.push(row);
-            }
-        }
-        Ok(matches)
+    fn search(&self, needle: &Needle) -> anyhow::Result<SearchJob> {
+        // Match against the parsed values, so that the results agree with what's
+        // on screen (eg. no matches in keys or escape sequences)
+        let columns = match needle.column() {
+            Some(column) => vec![column],
+            None => self
+                .schema
+                .fields()
+                .iter()
+                .map(|x| x.name().as_str())
+                .collect(),
+        };
+        let batches = self.column_batches(&columns)?;
+        Ok(search_batches(batches, needle, &self.settings))
+    }
+
+    fn column_batches(&self, columns: &[&str]) -> anyhow::Result<BatchIter> {
+        let fields = columns
+            .iter()
+            .map(|x| Ok(self.schema.field_with_name(x)?.clone()))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let rdr = ReaderBuilder::new(Schema::new(fields).into())
+            .with_batch_size(COLUMN_BATCH_SIZE)
+            .build(BufReader::new(self.fs.clone()))?;
+        Ok(Box::new(rdr.map(|x| Ok(x?))))
     }
 }
@@ -1,18 +1,112 @@
-#[cfg(feature = "json")]
+#[cfg(feature = "csv")]
 pub mod csv;
+pub mod freq;
+#[cfg(feature = "parquet")]
+pub mod hive;
+#[cfg(feature = "ipc")]
+pub mod ipc;
 #[cfg(feature = "json")]
 pub mod json;
+pub mod multi;
 #[cfg(feature = "parquet")]
 pub mod parquet;
 #[cfg(feature = "virt")]
 pub mod virt;
 
+use crate::search::{Needle, SearchJob};
+use crate::{draw::RenderSettings, format::CellFormatter, search::Progress};
+use arrow::array::ArrayRef;
+use arrow::datatypes::SchemaRef;
 use arrow::record_batch::RecordBatch;
 
 pub trait DataSource {
     fn check_for_new_rows(&mut self) -> anyhow::Result<usize>;
     fn row_count(&self) -> usize;
     fn fetch_batch(&mut self, offset: usize, len: usize) -> anyhow::Result<RecordBatch>;
-    /// Returns a list of rows containing the needle.  Should be sorted and de-duped.
-    fn search(&self, needle: &str) -> anyhow::Result<Vec<usize>>;
+    /// Returns a job which finds the rows containing a match for the needle.
+    /// The job will be run on a background thread.
+    fn search(&self, needle: &Needle) -> anyhow::Result<SearchJob>;
+    /// Reads every row of the given columns, in order.  The batches contain
+    /// just those columns.
+    fn column_batches(&self, columns: &[&str]) -> anyhow::Result<BatchIter>;
+}
+
+pub type BatchIter = Box<dyn Iterator<Item = anyhow::Result<RecordBatch>> + Send>;
+
+/// How many rows to read at a time in `column_batches()`
+#[cfg(any(feature = "csv", feature = "json"))]
+const COLUMN_BATCH_SIZE: usize = 8192;
+
+/// Returns a job which searches the batches.  They should cover every row,
+/// in order.
+#[cfg(any(feature = "csv", feature = "ipc", feature = "json"))]
+pub fn search_batches(batches: BatchIter, needle: &Needle, settings: &RenderSettings) -> SearchJob {
+    let needle = needle.clone();
+    let settings = settings.clone();
+    Box::new(move |progress| {
+        let mut first_row = 0;
+        for batch in batches {
+            let batch = batch?;
+            search_batch(&batch, &needle, &settings, first_row, progress)?;
+            first_row += batch.num_rows();
+            if !progress.update(first_row) {
+                break;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Finds the rows of `batch` which contain a match for the needle.  Values are
+/// formatted the same way they're rendered, and matched one at a time.  If the
+/// needle is scoped to a column, only that column is searched.  `first_row` is
+/// the index of the first row of the batch.
+pub fn search_batch(
+    batch: &RecordBatch,
+    needle: &Needle,
+    settings: &RenderSettings,
+    first_row: usize,
+    progress: &mut Progress,
+) -> anyhow::Result<()> {
+    let cols = match needle.column() {
+        Some(name) => vec![
+            batch
+                .column_by_name(name)
+                .ok_or_else(|| anyhow::anyhow!("No such column: {name}"))?,
+        ],
+        None => batch.columns().iter().collect(),
+    };
+    let fmts = cols
+        .into_iter()
+        .map(|col| CellFormatter::new(col, settings))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let mut buf = String::new();
+    for row in 0..batch.num_rows() {
+        let is_match = fmts.iter().any(|fmt| {
+            buf.clear();
+            fmt.write(&mut buf, row).is_ok() && needle.is_match(buf.as_bytes())
+        });
+        if is_match {
+            progress.push(first_row + row);
+        }
+    }
+    Ok(())
+}
+
+/// Casts the batch to the given schema.  Missing columns are filled with
+/// nulls.
+pub fn conform(batch: &RecordBatch, schema: &SchemaRef) -> anyhow::Result<RecordBatch> {
+    let cols = schema
+        .fields()
+        .iter()
+        .map(|field| match batch.column_by_name(field.name()) {
+            Some(col) if col.data_type() == field.data_type() => Ok(col.clone()),
+            Some(col) => Ok(arrow::compute::cast(col, field.data_type())?),
+            None => Ok(arrow::array::new_null_array(
+                field.data_type(),
+                batch.num_rows(),
+            )),
+        })
+        .collect::<anyhow::Result<Vec<ArrayRef>>>()?;
+    Ok(RecordBatch::try_new(schema.clone(), cols)?)
 }
@@ -0,0 +1,386 @@
+use super::{BatchIter, DataSource, conform};
+use crate::search::{Needle, SearchJob};
+use arrow::array::{ArrayRef, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaBuilder};
+use arrow::record_batch::{RecordBatch, RecordBatchOptions};
+use std::sync::Arc;
+use tracing::{debug, info};
+
+/// The name of the column which shows which file each row came from
+pub const FILE_COLUMN: &str = "_file";
+
+/// Several files, one after the other
+pub struct MultiSource {
+    parts: Vec<Part>,
+    /// The union of the parts' schemas, not including the partition keys or
+    /// the file column
+    schema: Arc<Schema>,
+    /// The names of the parts' partition keys, in order of appearance
+    keys: Vec<String>,
+    /// Whether to add a column showing which file each row came from
+    file_column: bool,
+}
+
+/// One of the files
+pub struct Part {
+    name: String,
+    inner: Box<dyn DataSource>,
+    /// Hive partition keys, which are shown as extra columns
+    keys: Vec<(String, Option<String>)>,
+    /// The columns of `inner`
+    columns: Vec<String>,
+}
+
+impl Part {
+    pub fn new(name: String, inner: Box<dyn DataSource>) -> Part {
+        Part {
+            name,
+            inner,
+            keys: vec![],
+            columns: vec![],
+        }
+    }
+
+    #[cfg(feature = "parquet")]
+    pub fn with_keys(mut self, keys: Vec<(String, Option<String>)>) -> Part {
+        self.keys = keys;
+        self
+    }
+
+    /// Peeks at the first row to find out which columns the part has.  Returns
+    /// its schema, or `None` if there aren't any rows yet.
+    fn learn_columns(&mut self) -> anyhow::Result<Option<Arc<Schema>>> {
+        if self.inner.row_count() == 0 {
+            return Ok(None);
+        }
+        let schema = self.inner.fetch_batch(0, 1)?.schema();
+        self.columns = schema.fields().iter().map(|x| x.name().clone()).collect();
+        Ok(Some(schema))
+    }
+
+    /// The values of the extra columns, which are the same for every row
+    fn constants(&self, file_column: bool) -> Vec<(String, Option<String>)> {
+        let mut xs = self.keys.clone();
+        if file_column {
+            xs.push((FILE_COLUMN.to_owned(), Some(self.name.clone())));
+        }
+        xs
+    }
+}
+
+impl MultiSource {
+    pub fn new(parts: Vec<Part>, file_column: bool) -> anyhow::Result<MultiSource> {
+        let mut this = MultiSource {
+            parts: vec![],
+            schema: Schema::empty().into(),
+            keys: vec![],
+            file_column,
+        };
+        for mut part in parts {
+            // If there aren't any rows yet, we'll find out the schema when
+            // there are
+            if let Some(schema) = part.learn_columns()? {
+                this.merge_schema(&schema);
+            }
+            for (key, _) in &part.keys {
+                if !this.keys.contains(key) {
+                    this.keys.push(key.clone());
+                }
+            }
+            this.parts.push(part);
+        }
+        Ok(this)
+    }
+
+    /// Merge `schema` into `self.schema`.  Columns are matched up by name, and
+    /// any with conflicting types are shown as strings.  Every column is
+    /// nullable, since it may be missing from some of the parts.
+    fn merge_schema(&mut self, schema: &Schema) {
+        let mut bldr = SchemaBuilder::from(self.schema.fields());
+        for new in schema.fields() {
+            let Some((idx, old)) = self.schema.fields().find(new.name()) else {
+                bldr.push(new.as_ref().clone().with_nullable(true));
+                continue;
+            };
+            let dtype = match (old.data_type(), new.data_type()) {
+                (x, DataType::Null) => x.clone(),
+                (DataType::Null, y) => y.clone(),
+                (x, y) if x == y => x.clone(),
+                (x, y) => {
+                    info!("{}: Can't unify {x} & {y}; casting to Utf8", old.name());
+                    DataType::Utf8
+                }
+            };
+            *bldr.field_mut(idx) = Field::new(old.name(), dtype, true).into();
+        }
+        self.schema = bldr.finish().into();
+        debug!("Merged new schema into the existing one");
+    }
+
+    /// The schema of the batches we return
+    fn output_schema(&self) -> Arc<Schema> {
+        let mut bldr = SchemaBuilder::from(self.schema.fields());
+        for key in &self.keys {
+            if self.schema.field_with_name(key).is_err() {
+                bldr.push(Field::new(key, DataType::Utf8, true));
+            }
+        }
+        if self.file_column {
+            bldr.push(Field::new(FILE_COLUMN, DataType::Utf8, false));
+        }
+        bldr.finish().into()
+    }
+
+    /// The row at which each part begins
+    fn part_offsets(&self) -> Vec<usize> {
+        let mut offset = 0;
+        self.parts
+            .iter()
+            .map(|part| {
+                let x = offset;
+                offset += part.inner.row_count();
+                x
+            })
+            .collect()
+    }
+}
+
+impl DataSource for MultiSource {
+    fn check_for_new_rows(&mut self) -> anyhow::Result<usize> {
+        let mut n = 0;
+        let mut new_schemas = vec![];
+        for part in &mut self.parts {
+            let was_empty = part.columns.is_empty();
+            n += part.inner.check_for_new_rows()?;
+            if was_empty && let Some(schema) = part.learn_columns()? {
+                new_schemas.push(schema);
+            }
+        }
+        for schema in new_schemas {
+            self.merge_schema(&schema);
+        }
+        Ok(n)
+    }
+
+    fn row_count(&self) -> usize {
+        self.parts.iter().map(|x| x.inner.row_count()).sum()
+    }
+
+    fn fetch_batch(&mut self, offset: usize, len: usize) -> anyhow::Result<RecordBatch> {
+        let offsets = self.part_offsets();
+        let mut batches = vec![];
+        for (idx, (part, part_start)) in self.parts.iter_mut().zip(offsets).enumerate() {
+            let part_end = part_start + part.inner.row_count();
+            let start = offset.max(part_start);
+            let end = (offset + len).min(part_end);
+            if start >= end {
+                continue;
+            }
+            let batch = part.inner.fetch_batch(start - part_start, end - start)?;
+            if part.columns.is_empty() {
+                part.columns = batch
+                    .schema()
+                    .fields()
+                    .iter()
+                    .map(|x| x.name().clone())
+                    .collect();
+            }
+            batches.push((idx, batch));
+        }
+        for (_, batch) in &batches {
+            self.merge_schema(&batch.schema());
+        }
+        let schema = self.output_schema();
+        let batches = batches
+            .into_iter()
+            .map(|(idx, batch)| {
+                let constants = self.parts[idx].constants(self.file_column);
+                conform(&with_constants(batch, &constants)?, &schema)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(arrow::compute::concat_batches(&schema, &batches)?)
+    }
+
+    fn search(&self, needle: &Needle) -> anyhow::Result<SearchJob> {
+        let mut jobs = vec![];
+        let mut first_row = 0;
+        for part in &self.parts {
+            let n_rows = part.inner.row_count();
+            let constants = part.constants(self.file_column);
+            // If a partition key or the file name matches, so does every row
+            let constant_matches = constants.iter().any(|(name, value)| {
+                needle.column().is_none_or(|x| x == name)
+                    && value
+                        .as_ref()
+                        .is_some_and(|x| needle.is_match(x.as_bytes()))
+            });
+            let job = if constant_matches {
+                None
+            } else if needle
+                .column()
+                .is_some_and(|x| !part.columns.iter().any(|c| c == x))
+            {
+                // The column isn't in this file
+                None
+            } else {
+                Some(part.inner.search(needle)?)
+            };
+            jobs.push((first_row, n_rows, constant_matches, job));
+            first_row += n_rows;
+        }
+        Ok(Box::new(move |progress| {
+            for (first_row, n_rows, all_match, job) in jobs {
+                if all_match {
+                    for row in first_row..first_row + n_rows {
+                        progress.push(row);
+                    }
+                }
+                if let Some(job) = job {
+                    progress.run_part(first_row, job)?;
+                }
+                if !progress.update(first_row + n_rows) {
+                    break;
+                }
+            }
+            Ok(())
+        }))
+    }
+
+    fn column_batches(&self, columns: &[&str]) -> anyhow::Result<BatchIter> {
+        let schema = self.output_schema();
+        let schema: Arc<Schema> = Arc::new(
+            schema.project(
+                &columns
+                    .iter()
+                    .map(|x| schema.index_of(x))
+                    .collect::<Result<Vec<_>, _>>()?,
+            )?,
+        );
+        let mut iters = vec![];
+        for part in &self.parts {
+            let part_columns = columns
+                .iter()
+                .copied()
+                .filter(|x| part.columns.iter().any(|c| c == x))
+                .collect::<Vec<_>>();
+            let batches = if part_columns.is_empty() {
+                // We still need to know how many rows there are
+                let first = part.columns.first().map(|x| vec![x.as_str()]);
+                part.inner.column_batches(&first.unwrap_or_default())?
+            } else {
+                part.inner.column_batches(&part_columns)?
+            };
+            let constants = part
+                .constants(self.file_column)
+                .into_iter()
+                .filter(|(name, _)| columns.contains(&name.as_str()))
+                .collect::<Vec<_>>();
+            let schema = schema.clone();
+            iters.push(
+                batches.map(move |batch| conform(&with_constants(batch?, &constants)?, &schema)),
+            );
+        }
+        Ok(Box::new(iters.into_iter().flatten()))
+    }
+}
+
+/// Appends columns which have the same value in every row
+fn with_constants(
+    batch: RecordBatch,
+    constants: &[(String, Option<String>)],
+) -> anyhow::Result<RecordBatch> {
+    if constants.is_empty() {
+        return Ok(batch);
+    }
+    let mut bldr = SchemaBuilder::from(batch.schema().fields());
+    let mut cols = batch.columns().to_vec();
+    for (name, value) in constants {
+        bldr.push(Field::new(name, DataType::Utf8, value.is_none()));
+        let col = StringArray::from(vec![value.as_deref(); batch.num_rows()]);
+        cols.push(Arc::new(col) as ArrayRef);
+    }
+    let opts = RecordBatchOptions::new().with_row_count(Some(batch.num_rows()));
+    Ok(RecordBatch::try_new_with_options(
+        bldr.finish().into(),
+        cols,
+        &opts,
+    )?)
+}
+
+#[cfg(all(test, feature = "csv"))]
+mod tests {
+    use super::*;
+    use crate::backend::csv::CsvFile;
+    use crate::draw::RenderSettings;
+    use crate::prompt::Dir;
+    use crate::search::{Search, SearchOpts, run_job};
+    use arrow::array::AsArray;
+    use std::io::Write;
+    use std::time::Duration;
+
+    fn csv(data: &str) -> anyhow::Result<Box<dyn DataSource>> {
+        let mut tmp = tempfile::tempfile()?;
+        tmp.write_all(data.as_bytes())?;
+        let mut source = CsvFile::new(tmp, b',', &RenderSettings::default())?;
+        source.check_for_new_rows()?;
+        Ok(Box::new(source))
+    }
+
+    #[test]
+    fn concatenates_parts() -> anyhow::Result<()> {
+        let parts = vec![
+            Part::new("one.csv".to_owned(), csv("a,b\n1,x\n2,y\n")?),
+            Part::new("two.csv".to_owned(), csv("b,c\nz,3\n")?),
+        ];
+        let mut source = MultiSource::new(parts, true)?;
+        assert_eq!(source.row_count(), 3);
+
+        let batch = source.fetch_batch(1, 2)?;
+        let names = batch
+            .schema()
+            .fields()
+            .iter()
+            .map(|x| x.name().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["a", "b", "c", FILE_COLUMN]);
+        assert_eq!(batch.column(0).null_count(), 1);
+        let b = batch.column(1).as_string::<i32>();
+        assert_eq!((b.value(0), b.value(1)), ("y", "z"));
+        let file = batch.column(3).as_string::<i32>();
+        assert_eq!((file.value(0), file.value(1)), ("one.csv", "two.csv"));
+
+        let search = |needle: &str| -> anyhow::Result<Vec<usize>> {
+            let needle = Needle::new(needle, SearchOpts::default())?;
+            let job = source.search(&needle)?;
+            let mut search = Search::spawn(job, needle, Dir::Forward, source.row_count(), None);
+            while search.is_running() {
+                std::thread::sleep(Duration::from_millis(1));
+                search.poll();
+            }
+            Ok(std::mem::take(&mut search.matches))
+        };
+        // Rows in later parts are offset by the length of the earlier ones
+        assert_eq!(search("z")?, [2]);
+        assert_eq!(search("one.csv")?, [0, 1]);
+        Ok(())
+    }
+
+    #[test]
+    fn finds_columns_of_late_parts() -> anyhow::Result<()> {
+        let mut tmp = tempfile::NamedTempFile::new()?;
+        let late = CsvFile::new(tmp.reopen()?, b',', &RenderSettings::default())?;
+        let parts = vec![
+            Part::new("one.csv".to_owned(), csv("a\n1\n")?),
+            Part::new("two.csv".to_owned(), Box::new(late)),
+        ];
+        let mut source = MultiSource::new(parts, false)?;
+
+        tmp.write_all(b"b\nfound\n")?;
+        source.check_for_new_rows()?;
+        assert_eq!(source.row_count(), 2);
+        assert!(source.output_schema().field_with_name("b").is_ok());
+        let needle = Needle::new("found", SearchOpts::default())?.in_column(Some("b".into()));
+        assert_eq!(run_job(source.search(&needle)?)?, [1]);
+        Ok(())
+    }
+}
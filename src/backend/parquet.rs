@@ -1,9 +1,16 @@
-use super::DataSource;
-use anyhow::anyhow;
+use super::{BatchIter, DataSource, search_batch};
+use crate::draw::RenderSettings;
+use crate::search::{Needle, SearchJob};
+use arrow::datatypes::DataType;
 use arrow::record_batch::RecordBatch;
-use parquet::arrow::arrow_reader::RowSelector;
+use parquet::arrow::ProjectionMask;
+use parquet::arrow::arrow_reader::{
+    ArrowReaderMetadata, ParquetRecordBatchReaderBuilder, RowSelector,
+};
+use parquet::file::metadata::RowGroupMetaData;
 use parquet::file::reader::FileReader;
 use parquet::file::serialized_reader::SerializedFileReader;
+use parquet::file::statistics::Statistics;
 use std::fs::File;
 use std::time::Instant;
 use tracing::debug;
@@ -11,14 +18,19 @@ use tracing::debug;
 pub struct ParquetFile {
     file: File,
     n_rows: usize,
+    settings: RenderSettings,
 }
 
 impl ParquetFile {
-    pub fn new(file: File) -> anyhow::Result<ParquetFile> {
+    pub fn new(file: File, settings: &RenderSettings) -> anyhow::Result<ParquetFile> {
         // We don't support live-updating parquet files, so we may as well cache
         // the row count
         let n_rows = count_rows(&file)?;
-        Ok(ParquetFile { file, n_rows })
+        Ok(ParquetFile {
+            file,
+            n_rows,
+            settings: settings.clone(),
+        })
     }
 }
 
@@ -53,8 +65,122 @@ impl DataSource for ParquetFile {
         Ok(batch)
     }
 
-    fn search(&self, _needle: &str) -> anyhow::Result<Vec<usize>> {
-        Err(anyhow!("Searching parquet not supported yet"))
+    fn search(&self, needle: &Needle) -> anyhow::Result<SearchJob> {
+        let file = self.file.try_clone()?;
+        let settings = self.settings.clone();
+        let needle = needle.clone();
+        Ok(Box::new(move |progress| {
+            let metadata = ArrowReaderMetadata::load(&file, Default::default())?;
+            // For column-scoped searches, we only need to decode that column
+            let projection = match needle.column() {
+                Some(name) => ProjectionMask::roots(
+                    metadata.metadata().file_metadata().schema_descr(),
+                    [metadata.schema().index_of(name)?],
+                ),
+                None => ProjectionMask::all(),
+            };
+            let mut first_row = 0;
+            for (idx, row_group) in metadata.metadata().row_groups().iter().enumerate() {
+                let n_rows = row_group.num_rows() as usize;
+                if !could_contain(&metadata, row_group, &needle) {
+                    debug!(idx, "Skipping row group");
+                    first_row += n_rows;
+                    continue;
+                }
+                let rdr = ParquetRecordBatchReaderBuilder::new_with_metadata(
+                    file.try_clone()?,
+                    metadata.clone(),
+                )
+                .with_row_groups(vec![idx])
+                .with_projection(projection.clone())
+                .build()?;
+                for batch in rdr {
+                    let batch = batch?;
+                    search_batch(&batch, &needle, &settings, first_row, progress)?;
+                    first_row += batch.num_rows();
+                    if !progress.update(first_row) {
+                        return Ok(());
+                    }
+                }
+            }
+            Ok(())
+        }))
+    }
+
+    fn column_batches(&self, columns: &[&str]) -> anyhow::Result<BatchIter> {
+        let bldr = ParquetRecordBatchReaderBuilder::try_new(self.file.try_clone()?)?;
+        let idxs = columns
+            .iter()
+            .map(|x| bldr.schema().index_of(x))
+            .collect::<Result<Vec<_>, _>>()?;
+        let projection = ProjectionMask::roots(bldr.parquet_schema(), idxs);
+        let rdr = bldr.with_projection(projection).build()?;
+        Ok(Box::new(rdr.map(|x| Ok(x?))))
+    }
+}
+
+/// Uses the row group's statistics to check whether any of its values could
+/// contain the needle, when formatted.  If this returns false, the row group
+/// can be skipped.
+fn could_contain(
+    metadata: &ArrowReaderMetadata,
+    row_group: &RowGroupMetaData,
+    needle: &Needle,
+) -> bool {
+    // We can only rule things out for plain strings
+    let Some(literal) = needle.literal() else {
+        return true;
+    };
+    let parquet_schema = metadata.metadata().file_metadata().schema_descr();
+    let arrow_schema = metadata.schema();
+    let mut fields = arrow_schema
+        .fields()
+        .iter()
+        .filter(|field| needle.column().is_none_or(|x| x == field.name()));
+    fields.any(|field| {
+        // Nested columns don't map onto a single parquet column
+        let Some((idx, _)) =
+            parquet::arrow::parquet_column(parquet_schema, arrow_schema, field.name())
+        else {
+            return true;
+        };
+        let stats = row_group.column(idx).statistics();
+        let all_null = stats
+            .and_then(|x| x.null_count_opt())
+            .is_some_and(|x| x == row_group.num_rows() as u64);
+        !all_null && could_contain_value(field.data_type(), stats, literal)
+    })
+}
+
+fn could_contain_value(dtype: &DataType, stats: Option<&Statistics>, needle: &str) -> bool {
+    let only = |chars: &str| needle.chars().all(|c| chars.contains(c));
+    match dtype {
+        DataType::Boolean => "true".contains(needle) || "false".contains(needle),
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 => {
+            // The longest value must be at least as long as the needle
+            let max_len = match stats {
+                Some(Statistics::Int32(x)) => x
+                    .min_opt()
+                    .zip(x.max_opt())
+                    .map(|(min, max)| min.to_string().len().max(max.to_string().len())),
+                Some(Statistics::Int64(x)) => x
+                    .min_opt()
+                    .zip(x.max_opt())
+                    .map(|(min, max)| min.to_string().len().max(max.to_string().len())),
+                _ => None,
+            };
+            only("-0123456789") && max_len.is_none_or(|x| x >= needle.len())
+        }
+        DataType::UInt8 | DataType::UInt16 | DataType::UInt32 | DataType::UInt64 => {
+            only("0123456789")
+        }
+        DataType::Float16 | DataType::Float32 | DataType::Float64 => {
+            only("-.0123456789") || "-inf".contains(needle) || "NaN".contains(needle)
+        }
+        DataType::Date32 | DataType::Date64 => only("-0123456789"),
+        DataType::Time32(_) | DataType::Time64(_) => only(":.0123456789"),
+        DataType::Timestamp(_, None) => only("-:. 0123456789"),
+        _ => true,
     }
 }
 
@@ -1,11 +1,22 @@
-use super::DataSource;
-use anyhow::anyhow;
-use arrow::datatypes::Schema;
+use super::{BatchIter, DataSource, search_batch};
+use crate::draw::RenderSettings;
+use crate::filter::Filter;
+use crate::search::{Needle, SearchJob};
+use anyhow::{anyhow, bail};
+use arrow::datatypes::{DataType, Schema};
 use arrow::record_batch::RecordBatch;
-use datafusion::prelude::{DataFrame, Expr};
-use std::path::Path;
+use datafusion::execution::options::ArrowReadOptions;
+use datafusion::functions_window::expr_fn::row_number;
+use datafusion::prelude::{
+    CsvReadOptions, DataFrame, NdJsonReadOptions, ParquetReadOptions, SessionConfig,
+    SessionContext, col,
+};
+use futures::StreamExt;
+use std::io::{Read, Write};
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Instant;
+use tempfile::NamedTempFile;
 use tokio::runtime::Runtime;
 use tracing::debug;
 
@@ -14,28 +25,125 @@ pub struct VirtualFile {
     schema: Arc<Schema>,
     df: DataFrame,
     n_rows: usize,
+    settings: RenderSettings,
+    /// Deleted when we're done with them
+    _snapshots: Vec<NamedTempFile>,
 }
 
-impl VirtualFile {
-    pub fn new(path: &Path, sort: &[String], filter: &[String]) -> anyhow::Result<VirtualFile> {
-        use datafusion::prelude::{ParquetReadOptions, SessionContext};
+/// The name under which the file is registered in SQL queries
+const TABLE_NAME: &str = "t";
+/// A temporary column used to break ties when sorting
+const ROW_NUMBER: &str = "__tass_row_number";
+
+/// Some files which DataFusion can read, all in the same format
+pub struct Table {
+    paths: Vec<PathBuf>,
+    format: String,
+    /// Hive partition keys, if any of the paths are directories
+    partition_cols: Vec<String>,
+    /// Copies of data which DataFusion can't read directly
+    snapshots: Vec<NamedTempFile>,
+}
+
+impl Table {
+    pub fn new(format: &str) -> Table {
+        Table {
+            paths: vec![],
+            format: format.to_owned(),
+            partition_cols: vec![],
+            snapshots: vec![],
+        }
+    }
+
+    pub fn add_file(&mut self, path: PathBuf) {
+        self.paths.push(path);
+    }
+
+    /// Adds a hive-partitioned directory.  `keys` are the names of the
+    /// partition keys, outermost first.
+    pub fn add_dir(&mut self, path: PathBuf, keys: Vec<String>) {
+        self.paths.push(path);
+        for key in keys {
+            if !self.partition_cols.contains(&key) {
+                self.partition_cols.push(key);
+            }
+        }
+    }
 
+    /// Copies the data into a tempfile, decompressing it if necessary.  This
+    /// blocks until all the data has been read.
+    pub fn add_snapshot(&mut self, rdr: impl Read + Send) -> anyhow::Result<()> {
+        let start = Instant::now();
+        let mut file = tempfile::Builder::new()
+            .prefix("tass-")
+            .suffix(&format!(".{}", self.format))
+            .tempfile()?;
+        let mut rdr = crate::decompress::decompressed(rdr)?;
+        let n_bytes = std::io::copy(&mut rdr, &mut file)?;
+        file.flush()?;
+        debug!(
+            "Took a {n_bytes}-byte snapshot (took {:?})",
+            start.elapsed()
+        );
+        self.paths.push(file.path().to_owned());
+        self.snapshots.push(file);
+        Ok(())
+    }
+}
+
+impl VirtualFile {
+    /// Registers the data as table `t`, and runs the query against it.  If
+    /// there's no query, the whole table is shown.
+    pub fn new(
+        table: Table,
+        sql: Option<&str>,
+        sort: &[String],
+        filter: &[String],
+        settings: &RenderSettings,
+    ) -> anyhow::Result<VirtualFile> {
         let rt = Runtime::new()?;
 
-        let ctx = SessionContext::new();
-        let opts = ParquetReadOptions::default();
-        let path = path.to_str().unwrap();
-        let mut df = rt.block_on(ctx.read_parquet(path, opts))?;
+        // `fetch_batch()` and `search()` run the query separately, and their
+        // rows need to come out in the same order.  With more than one
+        // partition, the batches of the scan can arrive in any order.
+        let config = SessionConfig::new().with_target_partitions(1);
+        let ctx = SessionContext::new_with_config(config);
+        rt.block_on(register_table(&ctx, &table))?;
+        let df = match sql {
+            Some(sql) => rt.block_on(ctx.sql(sql))?,
+            None => rt.block_on(ctx.table(TABLE_NAME))?,
+        };
+
+        VirtualFile::from_df(rt, df, table.snapshots, sort, filter, settings)
+    }
 
-        let schema = Arc::new(df.schema().into());
+    fn from_df(
+        rt: Runtime,
+        mut df: DataFrame,
+        snapshots: Vec<NamedTempFile>,
+        sort: &[String],
+        filter: &[String],
+        settings: &RenderSettings,
+    ) -> anyhow::Result<VirtualFile> {
+        let schema: Arc<Schema> = Arc::new(df.schema().into());
 
         if !sort.is_empty() {
-            let exprs = sort.iter().map(parse_sort_expr).collect();
-            df = df.sort(exprs)?;
+            // Ties are broken by position, so that the order is the same
+            // whether or not the query has a limit
+            let mut exprs = sort.iter().map(parse_sort_expr).collect::<Vec<_>>();
+            exprs.push(col(ROW_NUMBER).sort(true, true));
+            df = df
+                .with_column(ROW_NUMBER, row_number())?
+                .sort(exprs)?
+                .drop_columns(&[ROW_NUMBER])?;
         }
         let filters = filter
             .iter()
-            .map(|filter| parse_filter_expr(filter, &schema))
+            .map(|txt| {
+                let filter = Filter::parse(txt, &schema)
+                    .map_err(|e| anyhow!("Invalid filter: {e}\n{}", e.pointer(txt)))?;
+                filter.to_datafusion()
+            })
             .collect::<anyhow::Result<Vec<_>>>()?;
         if let Some(expr) = datafusion::logical_expr::utils::conjunction(filters) {
             df = df.filter(expr)?;
@@ -52,10 +160,72 @@ impl VirtualFile {
             schema,
             df,
             n_rows,
+            settings: settings.clone(),
+            _snapshots: snapshots,
         })
     }
 }
 
+async fn register_table(ctx: &SessionContext, table: &Table) -> anyhow::Result<()> {
+    let paths = table
+        .paths
+        .iter()
+        .map(|x| x.to_str().ok_or_else(|| anyhow!("Path is not valid UTF-8")))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    // DataFusion skips files which don't have the expected extension
+    let exts = table
+        .paths
+        .iter()
+        .map(|x| {
+            if x.is_dir() {
+                Some(table.format.as_str())
+            } else {
+                x.extension().and_then(|x| x.to_str())
+            }
+        })
+        .collect::<std::collections::HashSet<_>>();
+    let partition_cols = table
+        .partition_cols
+        .iter()
+        .map(|x| (x.clone(), DataType::Utf8))
+        .collect::<Vec<_>>();
+    let ext = match exts.into_iter().collect::<Vec<_>>().as_slice() {
+        [Some(x)] => format!(".{x}"),
+        _ => String::new(),
+    };
+    let df = match table.format.as_str() {
+        "parquet" => {
+            let opts = ParquetReadOptions {
+                file_extension: &ext,
+                table_partition_cols: partition_cols,
+                ..Default::default()
+            };
+            ctx.read_parquet(paths, opts).await?
+        }
+        "csv" | "tsv" => {
+            let delimiter = if table.format == "tsv" { b'\t' } else { b',' };
+            let opts = CsvReadOptions::new()
+                .delimiter(delimiter)
+                .file_extension(&ext);
+            ctx.read_csv(paths, opts).await?
+        }
+        "json" | "jsonl" | "ndjson" => {
+            let opts = NdJsonReadOptions::default().file_extension(&ext);
+            ctx.read_json(paths, opts).await?
+        }
+        "arrow" | "feather" | "ipc" => {
+            let opts = ArrowReadOptions {
+                file_extension: &ext,
+                ..Default::default()
+            };
+            ctx.read_arrow(paths, opts).await?
+        }
+        format => bail!("Can't query {format} files"),
+    };
+    ctx.register_table(TABLE_NAME, df.into_view())?;
+    Ok(())
+}
+
 impl DataSource for VirtualFile {
     fn check_for_new_rows(&mut self) -> anyhow::Result<usize> {
         Ok(0)
@@ -75,13 +245,45 @@ impl DataSource for VirtualFile {
         }
     }
 
-    fn search(&self, _needle: &str) -> anyhow::Result<Vec<usize>> {
-        Err(anyhow!("Searching virtual tables not supported yet"))
+    fn search(&self, needle: &Needle) -> anyhow::Result<SearchJob> {
+        // We stream the batches in the same order that `fetch_batch` sees them,
+        // so the row indices line up with the sorted/filtered view
+        let rt = self.rt.handle().clone();
+        let df = match needle.column() {
+            Some(name) => self.df.clone().select_columns(&[name])?,
+            None => self.df.clone(),
+        };
+        let settings = self.settings.clone();
+        let needle = needle.clone();
+        Ok(Box::new(move |progress| {
+            rt.block_on(async {
+                let mut stream = df.execute_stream().await?;
+                let mut first_row = 0;
+                while let Some(batch) = stream.next().await {
+                    let batch = batch?;
+                    search_batch(&batch, &needle, &settings, first_row, progress)?;
+                    first_row += batch.num_rows();
+                    if !progress.update(first_row) {
+                        break;
+                    }
+                }
+                Ok::<_, anyhow::Error>(())
+            })
+        }))
+    }
+
+    fn column_batches(&self, columns: &[&str]) -> anyhow::Result<BatchIter> {
+        let rt = self.rt.handle().clone();
+        let df = self.df.clone().select_columns(columns)?;
+        let mut stream = rt.block_on(df.execute_stream())?;
+        Ok(Box::new(std::iter::from_fn(move || {
+            let batch = rt.block_on(stream.next())?;
+            Some(batch.map_err(anyhow::Error::from))
+        })))
     }
 }
 
 fn parse_sort_expr(txt: &String) -> datafusion::logical_expr::SortExpr {
-    use datafusion::prelude::*;
     if let Some(txt) = txt.strip_prefix('-') {
         col(txt).sort(false, true)
     } else {
@@ -89,25 +291,42 @@ fn parse_sort_expr(txt: &String) -> datafusion::logical_expr::SortExpr {
     }
 }
 
-fn parse_filter_expr(txt: &str, schema: &Schema) -> anyhow::Result<Expr> {
-    use datafusion::logical_expr::Operator;
-    use datafusion::prelude::*;
-    use datafusion::scalar::ScalarValue;
-
-    let mut tokens = txt.split_whitespace();
-    let mut next_token = || tokens.next().ok_or_else(|| anyhow!("Not enough tokens"));
-    let col_name = next_token()?;
-    let field = schema.field_with_name(col_name)?;
-    let op = match next_token()? {
-        "=" | "==" => Operator::Eq,
-        "!=" => Operator::NotEq,
-        "<" => Operator::Lt,
-        "<=" => Operator::LtEq,
-        ">" => Operator::Gt,
-        ">=" => Operator::GtEq,
-        op => return Err(anyhow!("{op}: Invalid operator")),
-    };
-    let val = next_token()?;
-    let val = ScalarValue::try_from_string(val.to_owned(), field.data_type())?;
-    Ok(binary_expr(col(col_name), op, lit(val)))
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::{SearchOpts, run_job};
+    use arrow::array::AsArray;
+    use arrow::datatypes::Int64Type;
+
+    #[test]
+    fn search_matches_sorted_rows() -> anyhow::Result<()> {
+        // Lots of ties, spread across several files
+        let mut table = Table::new("csv");
+        let mut files = vec![];
+        for part in 0..4 {
+            let mut tmp = tempfile::Builder::new().suffix(".csv").tempfile()?;
+            writeln!(tmp, "key,val")?;
+            for i in 0..1000 {
+                writeln!(tmp, "{},{}", i % 3, part * 1000 + i)?;
+            }
+            table.add_file(tmp.path().to_owned());
+            files.push(tmp);
+        }
+        let settings = RenderSettings::default();
+        let mut source = VirtualFile::new(table, None, &["key".to_owned()], &[], &settings)?;
+
+        let opts = SearchOpts {
+            regex: true,
+            ..Default::default()
+        };
+        let needle = Needle::new("^12", opts)?.in_column(Some("val".into()));
+        let matches = run_job(source.search(&needle)?)?;
+        assert_eq!(matches.len(), 1 + 10 + 100);
+        for row in matches {
+            let batch = source.fetch_batch(row, 1)?;
+            let val = batch.column(1).as_primitive::<Int64Type>().value(0);
+            assert!(val.to_string().starts_with("12"), "row {row} is {val}");
+        }
+        Ok(())
+    }
 }
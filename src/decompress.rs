@@ -0,0 +1,107 @@
+use std::io::{BufRead, BufReader, Read};
+use tracing::info;
+
+#[derive(Debug, Copy, Clone)]
+enum Compression {
+    #[cfg(feature = "gzip")]
+    Gzip,
+    #[cfg(feature = "zstd")]
+    Zstd,
+    #[cfg(feature = "bzip2")]
+    Bzip2,
+    #[cfg(feature = "xz")]
+    Xz,
+}
+
+impl Compression {
+    fn from_extension(ext: &str) -> Option<Compression> {
+        match ext {
+            #[cfg(feature = "gzip")]
+            "gz" => Some(Compression::Gzip),
+            #[cfg(feature = "zstd")]
+            "zst" => Some(Compression::Zstd),
+            #[cfg(feature = "bzip2")]
+            "bz2" => Some(Compression::Bzip2),
+            #[cfg(feature = "xz")]
+            "xz" => Some(Compression::Xz),
+            _ => None,
+        }
+    }
+
+    fn from_magic(bytes: &[u8]) -> Option<Compression> {
+        match bytes {
+            #[cfg(feature = "gzip")]
+            [0x1f, 0x8b, ..] => Some(Compression::Gzip),
+            #[cfg(feature = "zstd")]
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(Compression::Zstd),
+            #[cfg(feature = "bzip2")]
+            [b'B', b'Z', b'h', ..] => Some(Compression::Bzip2),
+            #[cfg(feature = "xz")]
+            [0xfd, b'7', b'z', b'X', b'Z', 0x00, ..] => Some(Compression::Xz),
+            _ => None,
+        }
+    }
+
+    // With no codecs enabled, there's nothing to match on
+    #[cfg_attr(
+        not(any(feature = "gzip", feature = "zstd", feature = "bzip2", feature = "xz")),
+        allow(unused_variables, unreachable_code)
+    )]
+    fn decoder<'a>(
+        self,
+        rdr: impl BufRead + Send + 'a,
+    ) -> std::io::Result<Box<dyn Read + Send + 'a>> {
+        Ok(match self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Box::new(flate2::bufread::MultiGzDecoder::new(rdr)),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Box::new(zstd::stream::read::Decoder::with_buffer(rdr)?),
+            #[cfg(feature = "bzip2")]
+            Compression::Bzip2 => Box::new(bzip2::bufread::MultiBzDecoder::new(rdr)),
+            #[cfg(feature = "xz")]
+            Compression::Xz => Box::new(xz2::bufread::XzDecoder::new_multi_decoder(rdr)),
+        })
+    }
+}
+
+/// Whether files with this extension should be decompressed (eg. "gz")
+pub fn is_compressed_extension(ext: &str) -> bool {
+    Compression::from_extension(ext).is_some()
+}
+
+/// Wraps `rdr` in a decoder if its magic bytes indicate that it's compressed
+pub fn decompressed<'a>(rdr: impl Read + Send + 'a) -> std::io::Result<Box<dyn Read + Send + 'a>> {
+    let mut rdr = BufReader::new(rdr);
+    match Compression::from_magic(rdr.fill_buf()?) {
+        Some(compression) => {
+            info!("Decompressing {compression:?} data");
+            compression.decoder(rdr)
+        }
+        None => Ok(Box::new(rdr)),
+    }
+}
+
+#[cfg(all(test, feature = "gzip"))]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn detects_gzip() -> anyhow::Result<()> {
+        let data = "a,b\n1,2\n";
+        let mut enc = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        enc.write_all(data.as_bytes())?;
+        let compressed = enc.finish()?;
+
+        let mut out = String::new();
+        decompressed(compressed.as_slice())?.read_to_string(&mut out)?;
+        assert_eq!(out, data);
+
+        // Uncompressed data is passed through as-is
+        let mut out = String::new();
+        decompressed(data.as_bytes())?.read_to_string(&mut out)?;
+        assert_eq!(out, data);
+
+        Ok(())
+    }
+}
@@ -1,5 +1,8 @@
+use crate::format::*;
 use crate::prompt::Prompt;
+use crate::search::{Needle, Search};
 use crate::stats::*;
+use crate::summary::{ColumnSummary, QUANTILES};
 use arrow::{
     array::{
         Array, BooleanArray, GenericBinaryArray, GenericStringArray, OffsetSizeTrait,
@@ -7,20 +10,58 @@ use arrow::{
     },
     datatypes::*,
     record_batch::RecordBatch,
-    temporal_conversions,
 };
-use chrono::TimeZone;
-use chrono_tz::Tz;
 use crossterm::*;
-use std::{cmp::Ordering, collections::HashSet, fmt::Display, io::Write};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    io::Write,
+};
 use tracing::debug;
 
+/// The height of the header, except in the main grid (see
+/// `RenderSettings::header_height()`)
 pub const HEADER_HEIGHT: u16 = 1;
 pub const FOOTER_HEIGHT: u16 = 1;
+/// The background of the row under the cursor
+const CURSOR_ROW_BG: style::Color = style::Color::Rgb {
+    r: 0x30,
+    g: 0x30,
+    b: 0x30,
+};
+/// The background of the cell under the cursor
+const CURSOR_CELL_BG: style::Color = style::Color::Rgb {
+    r: 0x50,
+    g: 0x50,
+    b: 0x50,
+};
 
+#[derive(Clone)]
 pub struct RenderSettings {
     pub float_dps: usize,
     pub hide_empty: bool,
+    /// Draw a histogram above each column's name
+    pub histograms: bool,
+}
+
+/// The CLI's defaults
+#[cfg(test)]
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            float_dps: 5,
+            hide_empty: false,
+            histograms: false,
+        }
+    }
+}
+
+impl RenderSettings {
+    /// The number of lines above the grid
+    pub fn header_height(&self) -> u16 {
+        if self.histograms { 2 } else { 1 }
+    }
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -33,11 +74,15 @@ pub fn draw(
     idx_width: u16,
     col_widths: &[u16],
     total_rows: usize,
-    col_stats: &[ColumnStats],
+    col_stats: &[&ColumnStats],
+    n_pinned: usize,
     settings: &RenderSettings,
     prompt: &Prompt,
     highlights: &HashSet<usize>,
-    n_search_matches: usize,
+    sort: Option<(&str, bool)>,
+    search: Option<&Search>,
+    search_status: &str,
+    cursor: Option<(usize, usize)>,
 ) -> anyhow::Result<()> {
     debug!(
         n_rows = df.num_rows(),
@@ -45,6 +90,7 @@ pub fn draw(
         "Repainting!",
     );
 
+    let header_height = settings.header_height();
     stdout
         .queue(terminal::BeginSynchronizedUpdate)?
         .queue(terminal::Clear(terminal::ClearType::All))?;
@@ -53,7 +99,7 @@ pub fn draw(
     stdout
         .queue(style::SetAttribute(style::Attribute::Underlined))?
         .queue(style::SetAttribute(style::Attribute::Dim))?
-        .queue(cursor::MoveTo(0, HEADER_HEIGHT - 1))?
+        .queue(cursor::MoveTo(0, header_height - 1))?
         .queue(style::Print(" ".repeat(idx_width as usize)))?
         .queue(style::SetAttribute(style::Attribute::Reset))?;
 
@@ -62,13 +108,18 @@ pub fn draw(
     for x in start_row..(start_row + df.num_rows()) {
         stdout.queue(cursor::MoveToNextLine(1))?;
         let hl = highlights.contains(&x);
+        let is_match = search.is_some_and(|s| s.is_match(x));
+        if hl || is_match {
+            stdout.queue(style::SetAttribute(style::Attribute::Reset))?;
+        }
         if hl {
-            stdout
-                .queue(style::SetAttribute(style::Attribute::Reset))?
-                .queue(style::SetAttribute(style::Attribute::Bold))?;
+            stdout.queue(style::SetAttribute(style::Attribute::Bold))?;
+        }
+        if is_match {
+            stdout.queue(style::SetAttribute(style::Attribute::Reverse))?;
         }
         write!(stdout, "{}", x + 1)?;
-        if hl {
+        if hl || is_match {
             stdout
                 .queue(style::SetAttribute(style::Attribute::Reset))?
                 .queue(style::SetAttribute(style::Attribute::Dim))?;
@@ -78,7 +129,7 @@ pub fn draw(
 
     // Draw tildes for empty rows
     stdout.queue(style::SetForegroundColor(style::Color::Blue))?;
-    for _ in (df.num_rows() as u16)..(term_height - HEADER_HEIGHT - FOOTER_HEIGHT) {
+    for _ in (df.num_rows() as u16)..(term_height - header_height - FOOTER_HEIGHT) {
         stdout.queue(cursor::MoveToNextLine(1))?;
         write!(stdout, "~")?;
     }
@@ -86,25 +137,61 @@ pub fn draw(
 
     // Draw the header
     stdout
-        .queue(cursor::MoveTo(idx_width, HEADER_HEIGHT - 1))?
+        .queue(cursor::MoveTo(idx_width, header_height - 1))?
         .queue(style::SetAttribute(style::Attribute::Underlined))?
         .queue(style::SetAttribute(style::Attribute::Bold))?;
-    for (field, width) in df.schema().fields.iter().zip(col_widths) {
-        write!(stdout, "│ {:^w$} ", field.name(), w = *width as usize)?;
+    for (i, (field, width)) in df.schema().fields.iter().zip(col_widths).enumerate() {
+        let name = match sort {
+            Some((col, false)) if col == field.name() => format!("{} ↑", field.name()),
+            Some((col, true)) if col == field.name() => format!("{} ↓", field.name()),
+            _ => field.name().clone(),
+        };
+        let sep = separator(i, n_pinned);
+        write!(stdout, "{sep} ")?;
+        let is_cursor = cursor.is_some_and(|(_, col)| col == i);
+        if is_cursor {
+            stdout.queue(style::SetAttribute(style::Attribute::Reverse))?;
+        }
+        write!(stdout, "{:^w$}", name, w = *width as usize)?;
+        if is_cursor {
+            stdout.queue(style::SetAttribute(style::Attribute::NoReverse))?;
+        }
+        write!(stdout, " ")?;
     }
     stdout.queue(style::SetAttribute(style::Attribute::Reset))?;
 
+    // Draw the histograms above the header
+    if settings.histograms {
+        let mut x_baseline = idx_width;
+        for (stats, width) in col_stats.iter().zip(col_widths) {
+            stdout.queue(cursor::MoveTo(x_baseline + 2, header_height - 2))?;
+            match &stats.distribution {
+                Some(Distribution::Numeric { counts, .. }) => {
+                    stdout
+                        .queue(style::SetAttribute(style::Attribute::Dim))?
+                        .queue(style::Print(sparkline(counts, *width as usize)))?
+                        .queue(style::SetAttribute(style::Attribute::Reset))?;
+                }
+                Some(Distribution::Categorical(counts)) => {
+                    draw_category_bar(stdout, counts, *width)?;
+                }
+                None => (),
+            }
+            x_baseline += width + 3;
+        }
+    }
+
     // Draw the grid
     let mut x_baseline = idx_width;
     stdout.queue(style::SetAttribute(style::Attribute::Dim))?;
-    for width in col_widths {
+    for (i, width) in col_widths.iter().enumerate() {
         for row in 0..df.num_rows() {
             stdout
                 .queue(cursor::MoveTo(
                     x_baseline,
-                    u16::try_from(row).unwrap() + HEADER_HEIGHT,
+                    u16::try_from(row).unwrap() + header_height,
                 ))?
-                .queue(style::Print("│"))?;
+                .queue(style::Print(separator(i, n_pinned)))?;
         }
         x_baseline += width + 3;
     }
@@ -112,33 +199,241 @@ pub fn draw(
 
     // Draw the column data
     let mut x_baseline = idx_width;
-    for ((col, stats), width) in df.columns().iter().zip(col_stats).zip(col_widths) {
-        draw_col(stdout, stats, x_baseline, *width, col, settings)?;
+    let schema = df.schema();
+    for (((field, col), stats), width) in schema
+        .fields()
+        .iter()
+        .zip(df.columns())
+        .zip(col_stats)
+        .zip(col_widths)
+    {
+        // Column-scoped searches only highlight matches in their column
+        let needle = search
+            .map(|s| &s.needle)
+            .filter(|n| n.column().is_none_or(|x| x == field.name()));
+        draw_col(
+            stdout,
+            stats,
+            x_baseline,
+            header_height,
+            *width,
+            col,
+            settings,
+            needle,
+        )?;
         x_baseline += width + 3;
     }
 
+    // Redraw the row under the cursor with a lighter background
+    if let Some((row, cursor_col)) = cursor
+        && row < df.num_rows()
+    {
+        let y = u16::try_from(row).unwrap() + header_height;
+        stdout
+            .queue(cursor::MoveTo(0, y))?
+            .queue(style::SetBackgroundColor(CURSOR_ROW_BG))?
+            .queue(style::Print(format!(
+                "{:<w$}",
+                start_row + row + 1,
+                w = idx_width as usize
+            )))?;
+        let mut x_baseline = idx_width;
+        for (i, (((field, col), stats), width)) in schema
+            .fields()
+            .iter()
+            .zip(df.columns())
+            .zip(col_stats)
+            .zip(col_widths)
+            .enumerate()
+        {
+            let bg = if i == cursor_col {
+                CURSOR_CELL_BG
+            } else {
+                CURSOR_ROW_BG
+            };
+            stdout
+                .queue(cursor::MoveTo(x_baseline + 1, y))?
+                .queue(style::SetBackgroundColor(bg))?
+                .queue(style::Print(" ".repeat(*width as usize + 2)))?;
+            let needle = search
+                .map(|s| &s.needle)
+                .filter(|n| n.column().is_none_or(|x| x == field.name()));
+            let col = col.slice(row, 1);
+            draw_col(
+                stdout,
+                stats,
+                x_baseline,
+                y,
+                *width,
+                col.as_ref(),
+                settings,
+                needle,
+            )?;
+            x_baseline += width + 3;
+        }
+        stdout.queue(style::SetBackgroundColor(style::Color::Reset))?;
+    }
+
     // Draw the prompt
-    let search_txt = if n_search_matches != 0 {
-        format!("({} matches)", n_search_matches)
-    } else {
-        String::new()
-    };
     let location_txt = format!(
         "{}-{} of {}",
         start_row + 1,
         start_row + df.num_rows(),
         total_rows,
     );
-    let rprompt = format!("{search_txt} {location_txt}");
+    let rprompt = format!("{search_status} {location_txt}");
+    draw_rprompt(stdout, &rprompt, term_width, term_height)?;
+    stdout.queue(cursor::MoveTo(0, term_height))?;
+    prompt.draw(stdout)?;
+
+    stdout.queue(terminal::EndSynchronizedUpdate)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Like `draw`, but transposed: the fields run down the left, and each record
+/// is a column.  `col_stats` has one entry per field, and `cursor` is
+/// (record, field).  Histograms aren't drawn, so the header is always
+/// `HEADER_HEIGHT` rows.
+#[allow(clippy::too_many_arguments)]
+pub fn draw_transposed(
+    stdout: &mut impl Write,
+    start_row: usize,
+    df: RecordBatch,
+    term_width: u16,
+    term_height: u16,
+    name_width: u16,
+    record_width: u16,
+    total_rows: usize,
+    col_stats: &[&ColumnStats],
+    settings: &RenderSettings,
+    prompt: &Prompt,
+    highlights: &HashSet<usize>,
+    sort: Option<(&str, bool)>,
+    search: Option<&Search>,
+    search_status: &str,
+    cursor: Option<(usize, usize)>,
+) -> anyhow::Result<()> {
+    debug!(
+        n_rows = df.num_rows(),
+        n_cols = df.num_columns(),
+        "Repainting (transposed)!",
+    );
+
     stdout
-        .queue(cursor::MoveTo(
-            term_width - rprompt.len() as u16,
-            term_height,
-        ))?
-        .queue(style::SetAttribute(style::Attribute::Dim))?
-        .queue(style::Print(rprompt))?
-        .queue(style::SetAttribute(style::Attribute::Reset))?
-        .queue(cursor::MoveTo(0, term_height))?;
+        .queue(terminal::BeginSynchronizedUpdate)?
+        .queue(terminal::Clear(terminal::ClearType::All))?;
+
+    // Draw the header, which holds the row numbers
+    stdout
+        .queue(cursor::MoveTo(0, HEADER_HEIGHT - 1))?
+        .queue(style::SetAttribute(style::Attribute::Underlined))?
+        .queue(style::Print(" ".repeat(name_width as usize)))?;
+    for x in start_row..(start_row + df.num_rows()) {
+        write!(stdout, "│ ")?;
+        if highlights.contains(&x) {
+            stdout.queue(style::SetAttribute(style::Attribute::Bold))?;
+        } else {
+            stdout.queue(style::SetAttribute(style::Attribute::Dim))?;
+        }
+        if search.is_some_and(|s| s.is_match(x)) {
+            stdout.queue(style::SetAttribute(style::Attribute::Reverse))?;
+        }
+        write!(stdout, "{:^w$}", x + 1, w = record_width as usize)?;
+        stdout
+            .queue(style::SetAttribute(style::Attribute::Reset))?
+            .queue(style::SetAttribute(style::Attribute::Underlined))?;
+        write!(stdout, " ")?;
+    }
+    stdout.queue(style::SetAttribute(style::Attribute::Reset))?;
+
+    // Draw the field names
+    stdout.queue(style::SetAttribute(style::Attribute::Bold))?;
+    for (i, field) in df.schema().fields().iter().enumerate() {
+        let name = match sort {
+            Some((col, false)) if col == field.name() => format!("{} ↑", field.name()),
+            Some((col, true)) if col == field.name() => format!("{} ↓", field.name()),
+            _ => field.name().clone(),
+        };
+        let name = &name[..ceil_char_boundary(&name, name_width as usize)];
+        stdout.queue(cursor::MoveTo(0, u16::try_from(i).unwrap() + HEADER_HEIGHT))?;
+        let is_cursor = cursor.is_some_and(|(_, col)| col == i);
+        if is_cursor {
+            stdout.queue(style::SetAttribute(style::Attribute::Reverse))?;
+        }
+        write!(stdout, "{name}")?;
+        if is_cursor {
+            stdout.queue(style::SetAttribute(style::Attribute::NoReverse))?;
+        }
+    }
+    stdout.queue(style::SetAttribute(style::Attribute::Reset))?;
+
+    // Draw the grid
+    stdout.queue(style::SetAttribute(style::Attribute::Dim))?;
+    for row in 0..df.num_rows() {
+        let x = name_width + u16::try_from(row).unwrap() * (record_width + 3);
+        for i in 0..df.num_columns() {
+            stdout
+                .queue(cursor::MoveTo(x, u16::try_from(i).unwrap() + HEADER_HEIGHT))?
+                .queue(style::Print("│"))?;
+        }
+    }
+    stdout.queue(style::SetAttribute(style::Attribute::Reset))?;
+
+    // Draw the data, one cell at a time
+    let schema = df.schema();
+    for (i, ((field, col), stats)) in schema
+        .fields()
+        .iter()
+        .zip(df.columns())
+        .zip(col_stats)
+        .enumerate()
+    {
+        let y = u16::try_from(i).unwrap() + HEADER_HEIGHT;
+        let needle = search
+            .map(|s| &s.needle)
+            .filter(|n| n.column().is_none_or(|x| x == field.name()));
+        for row in 0..df.num_rows() {
+            let x_baseline = name_width + u16::try_from(row).unwrap() * (record_width + 3);
+            // The record under the cursor gets a lighter background
+            let bg = match cursor {
+                Some((r, c)) if r == row && c == i => Some(CURSOR_CELL_BG),
+                Some((r, _)) if r == row => Some(CURSOR_ROW_BG),
+                _ => None,
+            };
+            if let Some(bg) = bg {
+                stdout
+                    .queue(cursor::MoveTo(x_baseline + 1, y))?
+                    .queue(style::SetBackgroundColor(bg))?
+                    .queue(style::Print(" ".repeat(record_width as usize + 2)))?;
+            }
+            let col = col.slice(row, 1);
+            draw_col(
+                stdout,
+                stats,
+                x_baseline,
+                y,
+                record_width,
+                col.as_ref(),
+                settings,
+                needle,
+            )?;
+            if bg.is_some() {
+                stdout.queue(style::SetBackgroundColor(style::Color::Reset))?;
+            }
+        }
+    }
+
+    // Draw the prompt
+    let location_txt = format!(
+        "{}-{} of {}",
+        start_row + 1,
+        start_row + df.num_rows(),
+        total_rows,
+    );
+    let rprompt = format!("{search_status} {location_txt}");
+    draw_rprompt(stdout, &rprompt, term_width, term_height)?;
+    stdout.queue(cursor::MoveTo(0, term_height))?;
     prompt.draw(stdout)?;
 
     stdout.queue(terminal::EndSynchronizedUpdate)?;
@@ -146,13 +441,228 @@ pub fn draw(
     Ok(())
 }
 
+/// Draws every field of a single record, with values wrapped to fit the
+/// screen.  `scroll` is the number of lines to skip; it's clamped so that the
+/// last line is no higher than the bottom of the screen, and the clamped value
+/// is returned.
+#[allow(clippy::too_many_arguments)]
+pub fn draw_detail(
+    stdout: &mut impl Write,
+    record: &RecordBatch,
+    row: usize,
+    scroll: usize,
+    term_width: u16,
+    term_height: u16,
+    settings: &RenderSettings,
+    prompt: &Prompt,
+) -> anyhow::Result<usize> {
+    // (is_name, text)
+    let mut lines: Vec<(bool, String)> = vec![];
+    let wrap_width = (term_width as usize).saturating_sub(2).max(1);
+    for (field, col) in record.schema().fields().iter().zip(record.columns()) {
+        lines.push((true, format!("{} ({})", field.name(), field.data_type())));
+        if col.is_empty() || col.is_null(0) {
+            lines.push((false, "  null".to_owned()));
+            continue;
+        }
+        let mut buf = String::new();
+        CellFormatter::new(col.as_ref(), settings)?.write(&mut buf, 0)?;
+        for line in buf.lines() {
+            let chars = line.replace('\t', "    ").chars().collect::<Vec<_>>();
+            if chars.is_empty() {
+                lines.push((false, String::new()));
+            }
+            for chunk in chars.chunks(wrap_width) {
+                lines.push((false, format!("  {}", chunk.iter().collect::<String>())));
+            }
+        }
+    }
+
+    stdout
+        .queue(terminal::BeginSynchronizedUpdate)?
+        .queue(terminal::Clear(terminal::ClearType::All))?;
+
+    stdout
+        .queue(cursor::MoveTo(0, HEADER_HEIGHT - 1))?
+        .queue(style::SetAttribute(style::Attribute::Underlined))?
+        .queue(style::SetAttribute(style::Attribute::Bold))?;
+    let header = format!("Row {}", row + 1);
+    write!(stdout, "{:w$}", header, w = term_width as usize)?;
+    stdout.queue(style::SetAttribute(style::Attribute::Reset))?;
+
+    let height = (term_height - HEADER_HEIGHT - FOOTER_HEIGHT) as usize;
+    let scroll = scroll.min(lines.len().saturating_sub(height));
+    for (is_name, line) in lines.iter().skip(scroll).take(height) {
+        stdout.queue(cursor::MoveToNextLine(1))?;
+        if *is_name {
+            stdout.queue(style::SetAttribute(style::Attribute::Bold))?;
+        }
+        let line = &line[..ceil_char_boundary(line, term_width as usize)];
+        write!(stdout, "{line}")?;
+        stdout.queue(style::SetAttribute(style::Attribute::Reset))?;
+    }
+
+    let rprompt = format!(
+        "{}-{} of {} lines",
+        scroll + 1,
+        (scroll + height).min(lines.len()),
+        lines.len()
+    );
+    draw_rprompt(stdout, &rprompt, term_width, term_height)?;
+    stdout.queue(cursor::MoveTo(0, term_height))?;
+    prompt.draw(stdout)?;
+
+    stdout.queue(terminal::EndSynchronizedUpdate)?;
+    stdout.flush()?;
+    Ok(scroll)
+}
+
+/// Draws summary statistics for a column
+pub fn draw_summary(
+    stdout: &mut impl Write,
+    summary: &ColumnSummary,
+    term_width: u16,
+    term_height: u16,
+    settings: &RenderSettings,
+    prompt: &Prompt,
+) -> anyhow::Result<()> {
+    let s = &summary.summary;
+    let dps = settings.float_dps;
+    let approx = |exact: bool| if exact { "" } else { "≈ " };
+    let pct = |n: usize| n as f64 * 100.0 / s.count.max(1) as f64;
+
+    // (label, value)
+    let mut lines: Vec<(String, String)> = vec![
+        ("Count".into(), s.count.to_string()),
+        (
+            "Nulls".into(),
+            format!("{} ({:.1}%)", s.nulls, pct(s.nulls)),
+        ),
+        (
+            "Distinct".into(),
+            format!("{}{}", approx(s.distinct_is_exact), s.distinct),
+        ),
+    ];
+    lines.extend(s.min.clone().map(|x| ("Min".into(), x)));
+    lines.extend(s.max.clone().map(|x| ("Max".into(), x)));
+    lines.extend(s.mean.map(|x| ("Mean".into(), format!("{x:.dps$}"))));
+    lines.extend(s.stddev.map(|x| ("Std. dev.".into(), format!("{x:.dps$}"))));
+    for (q, x) in QUANTILES.iter().zip(&s.quantiles) {
+        let label = format!("{:.0}%", q * 100.0);
+        lines.push((label, format!("{}{x}", approx(s.quantiles_are_exact))));
+    }
+    if !s.top.is_empty() {
+        lines.push((String::new(), String::new()));
+        let title = format!("{}Most common", approx(s.top_is_exact));
+        lines.push((title, String::new()));
+        for (x, n) in &s.top {
+            lines.push((format!("{n} ({:.1}%)", pct(*n)), x.clone()));
+        }
+    }
+
+    stdout
+        .queue(terminal::BeginSynchronizedUpdate)?
+        .queue(terminal::Clear(terminal::ClearType::All))?;
+
+    stdout
+        .queue(cursor::MoveTo(0, HEADER_HEIGHT - 1))?
+        .queue(style::SetAttribute(style::Attribute::Underlined))?
+        .queue(style::SetAttribute(style::Attribute::Bold))?;
+    let header = format!("{} ({})", summary.column, summary.dtype);
+    write!(stdout, "{:w$}", header, w = term_width as usize)?;
+    stdout.queue(style::SetAttribute(style::Attribute::Reset))?;
+
+    let label_width = lines.iter().map(|x| x.0.chars().count()).max().unwrap_or(0);
+    let height = (term_height - HEADER_HEIGHT - FOOTER_HEIGHT) as usize;
+    for (label, value) in lines.iter().take(height) {
+        stdout.queue(cursor::MoveToNextLine(1))?;
+        stdout.queue(style::SetAttribute(style::Attribute::Dim))?;
+        write!(stdout, "{label:>label_width$}  ")?;
+        stdout.queue(style::SetAttribute(style::Attribute::Reset))?;
+        // Only show the first line of multi-line values
+        let value = value.lines().next().unwrap_or("");
+        let max_len = (term_width as usize).saturating_sub(label_width + 2);
+        write!(stdout, "{}", &value[..ceil_char_boundary(value, max_len)])?;
+    }
+
+    let rprompt = summary.status();
+    draw_rprompt(stdout, &rprompt, term_width, term_height)?;
+    stdout.queue(cursor::MoveTo(0, term_height))?;
+    prompt.draw(stdout)?;
+
+    stdout.queue(terminal::EndSynchronizedUpdate)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// The line to the left of the ith column.  Pinned columns are separated from
+/// the rest by a heavier line.
+fn separator(i: usize, n_pinned: usize) -> &'static str {
+    if i > 0 && i == n_pinned { "┃" } else { "│" }
+}
+
+/// Draws a list of all the columns, showing which ones are visible.  The
+/// columns are `(name, type, visible)`.
+pub fn draw_column_picker(
+    stdout: &mut impl Write,
+    columns: &[(String, DataType, bool)],
+    cursor: usize,
+    term_width: u16,
+    term_height: u16,
+    prompt: &Prompt,
+) -> anyhow::Result<()> {
+    stdout
+        .queue(terminal::BeginSynchronizedUpdate)?
+        .queue(terminal::Clear(terminal::ClearType::All))?;
+
+    let name_width = columns.iter().map(|x| x.0.len()).max().unwrap_or(0).max(6);
+    stdout
+        .queue(cursor::MoveTo(0, HEADER_HEIGHT - 1))?
+        .queue(style::SetAttribute(style::Attribute::Underlined))?
+        .queue(style::SetAttribute(style::Attribute::Bold))?;
+    let header = format!("    {:name_width$} │ Type", "Column");
+    write!(stdout, "{:w$}", header, w = term_width as usize)?;
+    stdout.queue(style::SetAttribute(style::Attribute::Reset))?;
+
+    // Scroll so that the cursor is on-screen
+    let height = (term_height - HEADER_HEIGHT - FOOTER_HEIGHT) as usize;
+    let first = (cursor + 1).saturating_sub(height);
+    for (i, (name, dtype, visible)) in columns.iter().enumerate().skip(first).take(height) {
+        stdout.queue(cursor::MoveToNextLine(1))?;
+        if i == cursor {
+            stdout.queue(style::SetAttribute(style::Attribute::Reverse))?;
+        }
+        if !visible {
+            stdout.queue(style::SetAttribute(style::Attribute::Dim))?;
+        }
+        let mark = if *visible { "✓" } else { " " };
+        let line = format!(" {mark}  {name:name_width$} │ {dtype}");
+        let line = &line[..ceil_char_boundary(&line, term_width as usize)];
+        write!(stdout, "{line}")?;
+        stdout.queue(style::SetAttribute(style::Attribute::Reset))?;
+    }
+
+    let n_visible = columns.iter().filter(|x| x.2).count();
+    let rprompt = format!("{n_visible} of {} columns shown", columns.len());
+    draw_rprompt(stdout, &rprompt, term_width, term_height)?;
+    stdout.queue(cursor::MoveTo(0, term_height))?;
+    prompt.draw(stdout)?;
+
+    stdout.queue(terminal::EndSynchronizedUpdate)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
 fn draw_col(
     stdout: &mut impl Write,
     stats: &ColumnStats,
     x_baseline: u16,
+    y_baseline: u16,
     width: u16,
     col: &dyn Array,
     settings: &RenderSettings,
+    needle: Option<&Needle>,
 ) -> anyhow::Result<()> {
     macro_rules! col {
         () => {
@@ -162,125 +672,202 @@ fn draw_col(
 
     match col.data_type() {
         DataType::Null => Ok(()),
-        DataType::Boolean => draw_bool_col(stdout, x_baseline, width, col!()),
+        DataType::Boolean => draw_bool_col(stdout, x_baseline, y_baseline, width, col!(), needle),
 
-        DataType::Int8 => draw_int_col::<Int8Type>(stdout, x_baseline, width, col!()),
-        DataType::Int16 => draw_int_col::<Int16Type>(stdout, x_baseline, width, col!()),
-        DataType::Int32 => draw_int_col::<Int32Type>(stdout, x_baseline, width, col!()),
-        DataType::Int64 => draw_int_col::<Int64Type>(stdout, x_baseline, width, col!()),
-        DataType::UInt8 => draw_int_col::<UInt8Type>(stdout, x_baseline, width, col!()),
-        DataType::UInt16 => draw_int_col::<UInt16Type>(stdout, x_baseline, width, col!()),
-        DataType::UInt32 => draw_int_col::<UInt32Type>(stdout, x_baseline, width, col!()),
-        DataType::UInt64 => draw_int_col::<UInt64Type>(stdout, x_baseline, width, col!()),
-        DataType::Float16 => {
-            draw_float_col::<Float16Type>(stdout, x_baseline, width, col!(), settings)
+        DataType::Int8 => {
+            draw_int_col::<Int8Type>(stdout, x_baseline, y_baseline, width, col!(), needle)
         }
-        DataType::Float32 => {
-            draw_float_col::<Float32Type>(stdout, x_baseline, width, col!(), settings)
+        DataType::Int16 => {
+            draw_int_col::<Int16Type>(stdout, x_baseline, y_baseline, width, col!(), needle)
         }
-        DataType::Float64 => {
-            draw_float_col::<Float64Type>(stdout, x_baseline, width, col!(), settings)
+        DataType::Int32 => {
+            draw_int_col::<Int32Type>(stdout, x_baseline, y_baseline, width, col!(), needle)
         }
-        DataType::Decimal128(_, _) => fallback(stdout, x_baseline, width, col),
-        DataType::Decimal256(_, _) => fallback(stdout, x_baseline, width, col),
+        DataType::Int64 => {
+            draw_int_col::<Int64Type>(stdout, x_baseline, y_baseline, width, col!(), needle)
+        }
+        DataType::UInt8 => {
+            draw_int_col::<UInt8Type>(stdout, x_baseline, y_baseline, width, col!(), needle)
+        }
+        DataType::UInt16 => {
+            draw_int_col::<UInt16Type>(stdout, x_baseline, y_baseline, width, col!(), needle)
+        }
+        DataType::UInt32 => {
+            draw_int_col::<UInt32Type>(stdout, x_baseline, y_baseline, width, col!(), needle)
+        }
+        DataType::UInt64 => {
+            draw_int_col::<UInt64Type>(stdout, x_baseline, y_baseline, width, col!(), needle)
+        }
+        DataType::Float16 => draw_float_col::<Float16Type>(
+            stdout,
+            x_baseline,
+            y_baseline,
+            width,
+            col!(),
+            settings,
+            needle,
+        ),
+        DataType::Float32 => draw_float_col::<Float32Type>(
+            stdout,
+            x_baseline,
+            y_baseline,
+            width,
+            col!(),
+            settings,
+            needle,
+        ),
+        DataType::Float64 => draw_float_col::<Float64Type>(
+            stdout,
+            x_baseline,
+            y_baseline,
+            width,
+            col!(),
+            settings,
+            needle,
+        ),
+        DataType::Decimal128(_, _) => fallback(stdout, x_baseline, y_baseline, width, col, needle),
+        DataType::Decimal256(_, _) => fallback(stdout, x_baseline, y_baseline, width, col, needle),
 
         DataType::Timestamp(TimeUnit::Second, tz) => draw_timestamp_col::<TimestampSecondType>(
             stdout,
             x_baseline,
+            y_baseline,
             width,
             col!(),
             tz.as_deref(),
+            needle,
         ),
         DataType::Timestamp(TimeUnit::Millisecond, tz) => {
             draw_timestamp_col::<TimestampMillisecondType>(
                 stdout,
                 x_baseline,
+                y_baseline,
                 width,
                 col!(),
                 tz.as_deref(),
+                needle,
             )
         }
         DataType::Timestamp(TimeUnit::Microsecond, tz) => {
             draw_timestamp_col::<TimestampMicrosecondType>(
                 stdout,
                 x_baseline,
+                y_baseline,
                 width,
                 col!(),
                 tz.as_deref(),
+                needle,
             )
         }
         DataType::Timestamp(TimeUnit::Nanosecond, tz) => {
             draw_timestamp_col::<TimestampNanosecondType>(
                 stdout,
                 x_baseline,
+                y_baseline,
                 width,
                 col!(),
                 tz.as_deref(),
+                needle,
             )
         }
-        DataType::Date32 => draw_date_col::<Date32Type>(stdout, x_baseline, width, col!()),
-        DataType::Date64 => draw_date_col::<Date64Type>(stdout, x_baseline, width, col!()),
-        DataType::Time32(TimeUnit::Second) => {
-            draw_time_col::<Time32SecondType>(stdout, x_baseline, width, col!())
+        DataType::Date32 => {
+            draw_date_col::<Date32Type>(stdout, x_baseline, y_baseline, width, col!(), needle)
         }
-        DataType::Time32(TimeUnit::Millisecond) => {
-            draw_time_col::<Time32MillisecondType>(stdout, x_baseline, width, col!())
+        DataType::Date64 => {
+            draw_date_col::<Date64Type>(stdout, x_baseline, y_baseline, width, col!(), needle)
+        }
+        DataType::Time32(TimeUnit::Second) => {
+            draw_time_col::<Time32SecondType>(stdout, x_baseline, y_baseline, width, col!(), needle)
         }
+        DataType::Time32(TimeUnit::Millisecond) => draw_time_col::<Time32MillisecondType>(
+            stdout,
+            x_baseline,
+            y_baseline,
+            width,
+            col!(),
+            needle,
+        ),
         DataType::Time32(TimeUnit::Microsecond | TimeUnit::Nanosecond) => {
             unreachable!()
         }
         DataType::Time64(TimeUnit::Second | TimeUnit::Millisecond) => {
             unreachable!()
         }
-        DataType::Time64(TimeUnit::Microsecond) => {
-            draw_time_col::<Time64MicrosecondType>(stdout, x_baseline, width, col!())
-        }
-        DataType::Time64(TimeUnit::Nanosecond) => {
-            draw_time_col::<Time64NanosecondType>(stdout, x_baseline, width, col!())
-        }
-        DataType::Duration(_) => fallback(stdout, x_baseline, width, col),
-        DataType::Interval(_) => fallback(stdout, x_baseline, width, col),
+        DataType::Time64(TimeUnit::Microsecond) => draw_time_col::<Time64MicrosecondType>(
+            stdout,
+            x_baseline,
+            y_baseline,
+            width,
+            col!(),
+            needle,
+        ),
+        DataType::Time64(TimeUnit::Nanosecond) => draw_time_col::<Time64NanosecondType>(
+            stdout,
+            x_baseline,
+            y_baseline,
+            width,
+            col!(),
+            needle,
+        ),
+        DataType::Duration(_) => fallback(stdout, x_baseline, y_baseline, width, col, needle),
+        DataType::Interval(_) => fallback(stdout, x_baseline, y_baseline, width, col, needle),
 
         DataType::Utf8 => draw_utf8_col::<i32>(
             stdout,
             x_baseline,
+            y_baseline,
             width,
             col!(),
             stats.cardinality.is_some(),
+            needle,
         ),
         DataType::LargeUtf8 => draw_utf8_col::<i64>(
             stdout,
             x_baseline,
+            y_baseline,
             width,
             col!(),
             stats.cardinality.is_some(),
+            needle,
         ),
-        DataType::Utf8View => fallback(stdout, x_baseline, width, col),
+        DataType::Utf8View => fallback(stdout, x_baseline, y_baseline, width, col, needle),
 
-        DataType::Binary => draw_binary_col::<i32>(stdout, x_baseline, width, col!()),
-        DataType::LargeBinary => draw_binary_col::<i64>(stdout, x_baseline, width, col!()),
-        DataType::FixedSizeBinary(_) => fallback(stdout, x_baseline, width, col),
-        DataType::BinaryView => fallback(stdout, x_baseline, width, col),
+        DataType::Binary => {
+            draw_binary_col::<i32>(stdout, x_baseline, y_baseline, width, col!(), needle)
+        }
+        DataType::LargeBinary => {
+            draw_binary_col::<i64>(stdout, x_baseline, y_baseline, width, col!(), needle)
+        }
+        DataType::FixedSizeBinary(_) => {
+            fallback(stdout, x_baseline, y_baseline, width, col, needle)
+        }
+        DataType::BinaryView => fallback(stdout, x_baseline, y_baseline, width, col, needle),
 
-        DataType::List(_) => fallback(stdout, x_baseline, width, col),
-        DataType::FixedSizeList(_, _) => fallback(stdout, x_baseline, width, col),
-        DataType::LargeList(_) => fallback(stdout, x_baseline, width, col),
-        DataType::ListView(_) => fallback(stdout, x_baseline, width, col),
-        DataType::LargeListView(_) => fallback(stdout, x_baseline, width, col),
+        DataType::List(_) => fallback(stdout, x_baseline, y_baseline, width, col, needle),
+        DataType::FixedSizeList(_, _) => {
+            fallback(stdout, x_baseline, y_baseline, width, col, needle)
+        }
+        DataType::LargeList(_) => fallback(stdout, x_baseline, y_baseline, width, col, needle),
+        DataType::ListView(_) => fallback(stdout, x_baseline, y_baseline, width, col, needle),
+        DataType::LargeListView(_) => fallback(stdout, x_baseline, y_baseline, width, col, needle),
 
-        DataType::Struct(_) => fallback(stdout, x_baseline, width, col),
-        DataType::Union(_, _) => fallback(stdout, x_baseline, width, col),
-        DataType::Dictionary(_, _) => fallback(stdout, x_baseline, width, col),
-        DataType::Map(_, _) => fallback(stdout, x_baseline, width, col),
-        DataType::RunEndEncoded(_, _) => fallback(stdout, x_baseline, width, col),
+        DataType::Struct(_) => fallback(stdout, x_baseline, y_baseline, width, col, needle),
+        DataType::Union(_, _) => fallback(stdout, x_baseline, y_baseline, width, col, needle),
+        DataType::Dictionary(_, _) => fallback(stdout, x_baseline, y_baseline, width, col, needle),
+        DataType::Map(_, _) => fallback(stdout, x_baseline, y_baseline, width, col, needle),
+        DataType::RunEndEncoded(_, _) => {
+            fallback(stdout, x_baseline, y_baseline, width, col, needle)
+        }
     }
 }
 
 fn fallback(
     stdout: &mut impl Write,
     x_baseline: u16,
+    y_baseline: u16,
     width: u16,
     col: &dyn Array,
+    needle: Option<&Needle>,
 ) -> anyhow::Result<()> {
     use arrow::util::display::*;
     let options = FormatOptions::default();
@@ -289,9 +876,9 @@ fn fallback(
         let txt = formatter.value(row).to_string();
         stdout.queue(cursor::MoveTo(
             x_baseline + 2,
-            u16::try_from(row).unwrap() + HEADER_HEIGHT,
+            u16::try_from(row).unwrap() + y_baseline,
         ))?;
-        print_text(stdout, &txt, width)?;
+        print_text(stdout, &txt, width, needle)?;
     }
     Ok(())
 }
@@ -306,28 +893,76 @@ fn oklch_to_color(oklch: [f32; 3]) -> style::Color {
     }
 }
 
+/// Values of categorical columns get a colour based on their hash
+fn category_color(val: &str) -> style::Color {
+    let mut hash = 7;
+    for byte in val.bytes() {
+        hash = ((hash << 5) + hash) + byte;
+    }
+    oklch_to_color([0.9, 0.07, hash as f32 * 360. / 255.])
+}
+
+/// A histogram made of block characters, `width` chars wide
+fn sparkline(counts: &[u64], width: usize) -> String {
+    const BLOCKS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let sums = (0..width)
+        .map(|i| {
+            let start = i * counts.len() / width;
+            let end = ((i + 1) * counts.len() / width).max(start + 1);
+            counts[start..end.min(counts.len())].iter().sum::<u64>()
+        })
+        .collect::<Vec<_>>();
+    let max = sums.iter().copied().max().unwrap_or(0).max(1);
+    sums.into_iter()
+        .map(|x| BLOCKS[(x * 8).div_ceil(max) as usize])
+        .collect()
+}
+
+/// A bar divided between the values of a categorical column, in proportion to
+/// their frequency.  The most common values come first.
+fn draw_category_bar(
+    stdout: &mut impl Write,
+    counts: &HashMap<String, u64>,
+    width: u16,
+) -> anyhow::Result<()> {
+    let mut counts = counts.iter().collect::<Vec<_>>();
+    counts.sort_unstable_by(|(x1, n1), (x2, n2)| n2.cmp(n1).then_with(|| x1.cmp(x2)));
+    let total = counts.iter().map(|(_, n)| **n).sum::<u64>().max(1);
+    let mut seen = 0;
+    let mut drawn = 0;
+    for (val, n) in counts {
+        seen += n;
+        let end = (seen * width as u64 + total / 2) / total;
+        if end > drawn {
+            stdout
+                .queue(style::SetForegroundColor(category_color(val)))?
+                .queue(style::Print("█".repeat((end - drawn) as usize)))?;
+            drawn = end;
+        }
+    }
+    stdout.queue(style::SetForegroundColor(style::Color::Reset))?;
+    Ok(())
+}
+
 fn draw_utf8_col<T: OffsetSizeTrait>(
     stdout: &mut impl Write,
     x_baseline: u16,
+    y_baseline: u16,
     width: u16,
     col: &GenericStringArray<T>,
     is_categorical: bool,
+    needle: Option<&Needle>,
 ) -> anyhow::Result<()> {
     for (row, val) in col.iter().enumerate() {
         let Some(val) = val else { continue };
         stdout.queue(cursor::MoveTo(
             x_baseline + 2,
-            u16::try_from(row).unwrap() + HEADER_HEIGHT,
+            u16::try_from(row).unwrap() + y_baseline,
         ))?;
         if is_categorical {
-            let mut hash = 7;
-            for byte in val.bytes() {
-                hash = ((hash << 5) + hash) + byte;
-            }
-            let fg = oklch_to_color([0.9, 0.07, hash as f32 * 360. / 255.]);
-            stdout.queue(style::SetForegroundColor(fg))?;
+            stdout.queue(style::SetForegroundColor(category_color(val)))?;
         }
-        print_text(stdout, val, width)?;
+        print_text(stdout, val, width, needle)?;
         if is_categorical {
             stdout.queue(style::SetForegroundColor(style::Color::Reset))?;
         }
@@ -339,17 +974,21 @@ fn draw_utf8_col<T: OffsetSizeTrait>(
 fn draw_binary_col<T: OffsetSizeTrait>(
     stdout: &mut impl Write,
     x_baseline: u16,
+    y_baseline: u16,
     width: u16,
     col: &GenericBinaryArray<T>,
+    needle: Option<&Needle>,
 ) -> anyhow::Result<()> {
+    let mut buf = String::new();
     for (row, val) in col.iter().enumerate() {
         let Some(val) = val else { continue };
-        let txt = val.escape_ascii().to_string();
+        buf.clear();
+        fmt_binary(&mut buf, val)?;
         stdout.queue(cursor::MoveTo(
             x_baseline + 2,
-            u16::try_from(row).unwrap() + HEADER_HEIGHT,
+            u16::try_from(row).unwrap() + y_baseline,
         ))?;
-        print_text(stdout, &txt, width)?;
+        print_text(stdout, &buf, width, needle)?;
     }
 
     Ok(())
@@ -358,8 +997,10 @@ fn draw_binary_col<T: OffsetSizeTrait>(
 fn draw_int_col<T: ArrowPrimitiveType>(
     stdout: &mut impl Write,
     x_baseline: u16,
+    y_baseline: u16,
     width: u16,
     col: &PrimitiveArray<T>,
+    needle: Option<&Needle>,
 ) -> anyhow::Result<()>
 where
     T::Native: Display,
@@ -372,13 +1013,10 @@ where
         let Some(val) = val else { continue };
         stdout.queue(cursor::MoveTo(
             x_baseline + 2,
-            u16::try_from(row).unwrap() + HEADER_HEIGHT,
+            u16::try_from(row).unwrap() + y_baseline,
         ))?;
-        {
-            buf.clear();
-            use std::fmt::Write;
-            write!(&mut buf, "{val}")?;
-        }
+        buf.clear();
+        fmt_display(&mut buf, val)?;
         // right-align
         let w = (width as usize).saturating_sub(buf.len());
         if w > 0 {
@@ -395,7 +1033,7 @@ where
             }
             Ordering::Greater => (),
         }
-        print_text(stdout, &buf, width)?;
+        print_text(stdout, &buf, width, needle)?;
         stdout.queue(style::SetForegroundColor(style::Color::Reset))?;
     }
 
@@ -405,9 +1043,11 @@ where
 fn draw_float_col<T: ArrowPrimitiveType>(
     stdout: &mut impl Write,
     x_baseline: u16,
+    y_baseline: u16,
     width: u16,
     col: &PrimitiveArray<T>,
     settings: &RenderSettings,
+    needle: Option<&Needle>,
 ) -> anyhow::Result<()>
 where
     T::Native: Display,
@@ -418,17 +1058,16 @@ where
         let Some(val) = val else { continue };
         stdout.queue(cursor::MoveTo(
             x_baseline + 2,
-            u16::try_from(row).unwrap() + HEADER_HEIGHT,
+            u16::try_from(row).unwrap() + y_baseline,
         ))?;
         buf.clear();
-        use std::fmt::Write;
-        write!(&mut buf, "{val:.prec$}", prec = settings.float_dps)?;
+        fmt_float(&mut buf, val, settings.float_dps)?;
         // right-align
         let w = (width as usize).saturating_sub(buf.len());
         if w > 0 {
             write!(stdout, "{:<w$}", " ", w = w)?;
         }
-        print_text(stdout, &buf, width)?;
+        print_text(stdout, &buf, width, needle)?;
     }
 
     Ok(())
@@ -437,8 +1076,10 @@ where
 fn draw_bool_col(
     stdout: &mut impl Write,
     x_baseline: u16,
+    y_baseline: u16,
     width: u16,
     col: &BooleanArray,
+    needle: Option<&Needle>,
 ) -> anyhow::Result<()> {
     let mut buf = String::new();
 
@@ -446,13 +1087,12 @@ fn draw_bool_col(
         let Some(val) = val else { continue };
         stdout.queue(cursor::MoveTo(
             x_baseline + 2,
-            u16::try_from(row).unwrap() + HEADER_HEIGHT,
+            u16::try_from(row).unwrap() + y_baseline,
         ))?;
         buf.clear();
-        use std::fmt::Write;
         // TODO: Colour
-        write!(&mut buf, "{val}")?;
-        print_text(stdout, &buf, width)?;
+        fmt_display(&mut buf, val)?;
+        print_text(stdout, &buf, width, needle)?;
     }
 
     Ok(())
@@ -461,31 +1101,26 @@ fn draw_bool_col(
 fn draw_timestamp_col<T: ArrowPrimitiveType>(
     stdout: &mut impl Write,
     x_baseline: u16,
+    y_baseline: u16,
     width: u16,
     col: &PrimitiveArray<T>,
     tz: Option<&str>,
+    needle: Option<&Needle>,
 ) -> anyhow::Result<()>
 where
     T::Native: Into<i64>,
 {
     let mut buf = String::new();
+    let tz = parse_tz(tz);
     for (row, val) in col.iter().enumerate() {
         let Some(val) = val else { continue };
         stdout.queue(cursor::MoveTo(
             x_baseline + 2,
-            u16::try_from(row).unwrap() + HEADER_HEIGHT,
+            u16::try_from(row).unwrap() + y_baseline,
         ))?;
         buf.clear();
-        use std::fmt::Write;
-        let datetime = temporal_conversions::as_datetime::<T>(val.into()).unwrap();
-        if let Some(tz) = tz {
-            let tz: Tz = tz.parse().unwrap();
-            let datetime = tz.from_utc_datetime(&datetime);
-            write!(&mut buf, "{datetime}")?;
-        } else {
-            write!(&mut buf, "{datetime}")?;
-        }
-        print_text(stdout, &buf, width)?;
+        fmt_timestamp::<T>(&mut buf, val.into(), tz)?;
+        print_text(stdout, &buf, width, needle)?;
     }
 
     Ok(())
@@ -494,8 +1129,10 @@ where
 fn draw_date_col<T: ArrowPrimitiveType>(
     stdout: &mut impl Write,
     x_baseline: u16,
+    y_baseline: u16,
     width: u16,
     col: &PrimitiveArray<T>,
+    needle: Option<&Needle>,
 ) -> anyhow::Result<()>
 where
     T::Native: Into<i64>,
@@ -506,13 +1143,11 @@ where
         let Some(val) = val else { continue };
         stdout.queue(cursor::MoveTo(
             x_baseline + 2,
-            u16::try_from(row).unwrap() + HEADER_HEIGHT,
+            u16::try_from(row).unwrap() + y_baseline,
         ))?;
         buf.clear();
-        use std::fmt::Write;
-        let date = temporal_conversions::as_date::<T>(val.into()).unwrap();
-        write!(&mut buf, "{date}")?;
-        print_text(stdout, &buf, width)?;
+        fmt_date::<T>(&mut buf, val.into())?;
+        print_text(stdout, &buf, width, needle)?;
     }
 
     Ok(())
@@ -521,8 +1156,10 @@ where
 fn draw_time_col<T: ArrowPrimitiveType>(
     stdout: &mut impl Write,
     x_baseline: u16,
+    y_baseline: u16,
     width: u16,
     col: &PrimitiveArray<T>,
+    needle: Option<&Needle>,
 ) -> anyhow::Result<()>
 where
     T::Native: Into<i64>,
@@ -532,19 +1169,26 @@ where
         let Some(val) = val else { continue };
         stdout.queue(cursor::MoveTo(
             x_baseline + 2,
-            u16::try_from(row).unwrap() + HEADER_HEIGHT,
+            u16::try_from(row).unwrap() + y_baseline,
         ))?;
         buf.clear();
-        use std::fmt::Write;
-        let time = temporal_conversions::as_time::<T>(val.into()).unwrap();
-        write!(&mut buf, "{time}")?;
-        print_text(stdout, &buf, width)?;
+        fmt_time::<T>(&mut buf, val.into())?;
+        print_text(stdout, &buf, width, needle)?;
     }
 
     Ok(())
 }
 
-fn print_text(stdout: &mut impl Write, mut txt: &str, width: u16) -> anyhow::Result<()> {
+/// Prints the text, truncating it to fit in `width`.  Matches for the needle
+/// are shown in reverse video.  If a match has been truncated away entirely,
+/// the truncation marker is highlighted instead.
+fn print_text(
+    stdout: &mut impl Write,
+    mut txt: &str,
+    width: u16,
+    needle: Option<&Needle>,
+) -> anyhow::Result<()> {
+    let matches = needle.map_or(vec![], |x| x.find_iter(txt).collect::<Vec<_>>());
     let mut truncated = false;
     if let Some(idx) = txt.find('\n') {
         txt = &txt[..idx];
@@ -555,18 +1199,54 @@ fn print_text(stdout: &mut impl Write, mut txt: &str, width: u16) -> anyhow::Res
         txt = &txt[..slice_until];
         truncated = true;
     }
+    let mut pos = 0;
+    for m in matches.iter().take_while(|m| m.start < txt.len()) {
+        let end = m.end.min(txt.len());
+        stdout
+            .queue(style::Print(&txt[pos..m.start]))?
+            .queue(style::SetAttribute(style::Attribute::Reverse))?
+            .queue(style::Print(&txt[m.start..end]))?
+            .queue(style::SetAttribute(style::Attribute::NoReverse))?;
+        pos = end;
+    }
+    stdout.queue(style::Print(&txt[pos..]))?;
     if truncated {
+        let hidden_match = matches.last().is_some_and(|m| m.start >= txt.len());
+        if hidden_match {
+            stdout
+                .queue(style::SetForegroundColor(style::Color::Yellow))?
+                .queue(style::SetAttribute(style::Attribute::Bold))?;
+        }
         stdout
-            .queue(style::Print(txt))?
             .queue(style::SetAttribute(style::Attribute::Reverse))?
             .queue(style::Print(">"))?
             .queue(style::SetAttribute(style::Attribute::Reset))?;
-    } else {
-        stdout.queue(style::Print(txt))?;
     }
     Ok(())
 }
 
+/// Draws the text at the bottom-right of the screen.  If it's too long, the
+/// start is cut off, since the end (eg. the location) matters most.
+fn draw_rprompt(
+    stdout: &mut impl Write,
+    txt: &str,
+    term_width: u16,
+    term_height: u16,
+) -> anyhow::Result<()> {
+    let len = txt.chars().count();
+    let start = len.saturating_sub(term_width as usize);
+    let txt = txt.chars().skip(start).collect::<String>();
+    stdout
+        .queue(cursor::MoveTo(
+            term_width - (len - start) as u16,
+            term_height,
+        ))?
+        .queue(style::SetAttribute(style::Attribute::Dim))?
+        .queue(style::Print(txt))?
+        .queue(style::SetAttribute(style::Attribute::Reset))?;
+    Ok(())
+}
+
 // Unstable library code copied from https://doc.rust-lang.org/stable/src/core/str/mod.rs.html#301
 pub fn ceil_char_boundary(text: &str, index: usize) -> usize {
     let is_utf8_char_boundary = |b: u8| -> bool {
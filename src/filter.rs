@@ -0,0 +1,765 @@
+use arrow::array::{Array, ArrayRef, BooleanArray, Datum, Scalar, StringArray};
+use arrow::compute::kernels::boolean::{and_kleene, is_not_null, is_null, not, or_kleene};
+use arrow::compute::kernels::cmp;
+use arrow::compute::kernels::comparison::{ilike, like, nilike, nlike};
+use arrow::compute::{CastOptions, cast, cast_with_options, prep_null_mask_filter};
+use arrow::datatypes::{DataType, Schema};
+use arrow::record_batch::RecordBatch;
+use std::fmt;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// A boolean filter expression, eg. `status != 'ok' and (latency > 100 or
+/// retries >= 3)`.
+///
+/// The syntax is a subset of SQL.  Strings go in single quotes, and column
+/// names which aren't plain identifiers go in double quotes (or backticks).
+/// Supported predicates are the comparisons (`=`, `!=`, `<`, `<=`, `>`, `>=`),
+/// `IS [NOT] NULL`, `[NOT] IN (...)`, and `[NOT] [I]LIKE`, and they can be
+/// combined with `AND`, `OR`, `NOT`, and parentheses.
+pub struct Filter {
+    expr: Expr,
+    columns: Vec<String>,
+}
+
+enum Expr {
+    Cmp(Operand, Op, Operand),
+    IsNull(Operand, bool),
+    InList(Operand, Vec<Operand>, bool),
+    Like {
+        value: Operand,
+        pattern: String,
+        case_insensitive: bool,
+        negated: bool,
+    },
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+enum Operand {
+    /// A column, cast to the given type if necessary
+    Column(String, DataType),
+    /// A single-element array
+    Value(ArrayRef),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+}
+
+impl Filter {
+    /// Parses the expression and checks it against the schema.  Literals are
+    /// converted to the type of the column they're compared with.
+    pub fn parse(txt: &str, schema: &Schema) -> Result<Filter, ParseError> {
+        let tokens = tokenize(txt)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            end: txt.len(),
+            schema,
+            columns: vec![],
+        };
+        let expr = parser.parse_or()?;
+        if parser.pos < parser.tokens.len() {
+            return Err(parser.unexpected("the end of the expression"));
+        }
+        Ok(Filter {
+            expr,
+            columns: parser.columns,
+        })
+    }
+
+    /// Keeps the rows where `column` has the (first) value in `value`, which
+    /// should be of the type the column is displayed as.  The value is compared
+    /// as-is, rather than being formatted and parsed again.
+    pub fn equals(column: &str, value: &dyn Array) -> anyhow::Result<Filter> {
+        let dtype = value.data_type();
+        anyhow::ensure!(!dtype.is_nested(), "Can't compare {dtype} values");
+        let col = Operand::Column(column.to_owned(), dtype.clone());
+        let expr = if value.is_null(0) {
+            Expr::IsNull(col, false)
+        } else {
+            Expr::Cmp(col, Op::Eq, Operand::Value(value.slice(0, 1)))
+        };
+        Ok(Filter {
+            expr,
+            columns: vec![column.to_owned()],
+        })
+    }
+
+    /// The columns which the filter looks at
+    pub fn columns(&self) -> Vec<&str> {
+        self.columns.iter().map(|x| x.as_str()).collect()
+    }
+
+    /// Which rows of the batch pass the filter.  The batch must contain the
+    /// columns returned by `columns()`.  Rows for which the expression is null
+    /// don't pass.
+    pub fn evaluate(&self, batch: &RecordBatch) -> anyhow::Result<BooleanArray> {
+        let mask = self.expr.evaluate(batch)?;
+        Ok(if mask.null_count() > 0 {
+            prep_null_mask_filter(&mask)
+        } else {
+            mask
+        })
+    }
+
+    /// Converts the filter into a DataFusion expression
+    #[cfg(feature = "virt")]
+    pub fn to_datafusion(&self) -> anyhow::Result<datafusion::prelude::Expr> {
+        self.expr.to_datafusion()
+    }
+}
+
+impl Expr {
+    fn evaluate(&self, batch: &RecordBatch) -> anyhow::Result<BooleanArray> {
+        let mask = match self {
+            Expr::Cmp(lhs, op, rhs) => {
+                let f = match op {
+                    Op::Eq => cmp::eq,
+                    Op::NotEq => cmp::neq,
+                    Op::Lt => cmp::lt,
+                    Op::LtEq => cmp::lt_eq,
+                    Op::Gt => cmp::gt,
+                    Op::GtEq => cmp::gt_eq,
+                };
+                f(lhs.datum(batch)?.as_ref(), rhs.datum(batch)?.as_ref())?
+            }
+            Expr::IsNull(x, negated) => {
+                let x = x.array(batch)?;
+                if *negated {
+                    is_not_null(&x)?
+                } else {
+                    is_null(&x)?
+                }
+            }
+            Expr::InList(x, list, negated) => {
+                let x = x.datum(batch)?;
+                let mut mask = BooleanArray::from(vec![false]);
+                for item in list {
+                    let eq = cmp::eq(x.as_ref(), item.datum(batch)?.as_ref())?;
+                    let len = mask.len().max(eq.len());
+                    mask = or_kleene(&broadcast(mask, len), &broadcast(eq, len))?;
+                }
+                if *negated { not(&mask)? } else { mask }
+            }
+            Expr::Like {
+                value,
+                pattern,
+                case_insensitive,
+                negated,
+            } => {
+                let f = match (case_insensitive, negated) {
+                    (false, false) => like,
+                    (false, true) => nlike,
+                    (true, false) => ilike,
+                    (true, true) => nilike,
+                };
+                let pattern = Scalar::new(StringArray::from(vec![pattern.as_str()]));
+                f(value.datum(batch)?.as_ref(), &pattern)?
+            }
+            Expr::Not(x) => not(&x.evaluate(batch)?)?,
+            Expr::And(lhs, rhs) => {
+                let (lhs, rhs) = (lhs.evaluate(batch)?, rhs.evaluate(batch)?);
+                let len = lhs.len().max(rhs.len());
+                and_kleene(&broadcast(lhs, len), &broadcast(rhs, len))?
+            }
+            Expr::Or(lhs, rhs) => {
+                let (lhs, rhs) = (lhs.evaluate(batch)?, rhs.evaluate(batch)?);
+                let len = lhs.len().max(rhs.len());
+                or_kleene(&broadcast(lhs, len), &broadcast(rhs, len))?
+            }
+        };
+        // Expressions which don't mention any columns give a single value
+        Ok(broadcast(mask, batch.num_rows()))
+    }
+
+    #[cfg(feature = "virt")]
+    fn to_datafusion(&self) -> anyhow::Result<datafusion::prelude::Expr> {
+        use datafusion::logical_expr::{Operator, binary_expr};
+        Ok(match self {
+            Expr::Cmp(lhs, op, rhs) => {
+                let op = match op {
+                    Op::Eq => Operator::Eq,
+                    Op::NotEq => Operator::NotEq,
+                    Op::Lt => Operator::Lt,
+                    Op::LtEq => Operator::LtEq,
+                    Op::Gt => Operator::Gt,
+                    Op::GtEq => Operator::GtEq,
+                };
+                binary_expr(lhs.to_datafusion()?, op, rhs.to_datafusion()?)
+            }
+            Expr::IsNull(x, false) => x.to_datafusion()?.is_null(),
+            Expr::IsNull(x, true) => x.to_datafusion()?.is_not_null(),
+            Expr::InList(x, list, negated) => {
+                let list = list
+                    .iter()
+                    .map(Operand::to_datafusion)
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                x.to_datafusion()?.in_list(list, *negated)
+            }
+            Expr::Like {
+                value,
+                pattern,
+                case_insensitive,
+                negated,
+            } => {
+                let value = value.to_datafusion()?;
+                let pattern = datafusion::prelude::lit(pattern.as_str());
+                match (case_insensitive, negated) {
+                    (false, false) => value.like(pattern),
+                    (false, true) => value.not_like(pattern),
+                    (true, false) => value.ilike(pattern),
+                    (true, true) => value.not_ilike(pattern),
+                }
+            }
+            Expr::Not(x) => !x.to_datafusion()?,
+            Expr::And(lhs, rhs) => lhs.to_datafusion()?.and(rhs.to_datafusion()?),
+            Expr::Or(lhs, rhs) => lhs.to_datafusion()?.or(rhs.to_datafusion()?),
+        })
+    }
+}
+
+/// If `mask` is a single value, repeats it `len` times
+fn broadcast(mask: BooleanArray, len: usize) -> BooleanArray {
+    if mask.len() != 1 || len == 1 {
+        return mask;
+    }
+    let value = mask.is_valid(0).then(|| mask.value(0));
+    BooleanArray::from(vec![value; len])
+}
+
+impl Operand {
+    fn array(&self, batch: &RecordBatch) -> anyhow::Result<ArrayRef> {
+        match self {
+            Operand::Column(name, dtype) => {
+                let col = batch
+                    .column_by_name(name)
+                    .ok_or_else(|| anyhow::anyhow!("No such column: {name}"))?;
+                if col.data_type() == dtype {
+                    Ok(col.clone())
+                } else {
+                    Ok(cast(col, dtype)?)
+                }
+            }
+            Operand::Value(x) => Ok(x.clone()),
+        }
+    }
+
+    fn datum(&self, batch: &RecordBatch) -> anyhow::Result<Box<dyn Datum>> {
+        let array = self.array(batch)?;
+        Ok(match self {
+            Operand::Column(..) => Box::new(array),
+            Operand::Value(_) => Box::new(Scalar::new(array)),
+        })
+    }
+
+    fn data_type(&self) -> &DataType {
+        match self {
+            Operand::Column(_, dtype) => dtype,
+            Operand::Value(x) => x.data_type(),
+        }
+    }
+
+    #[cfg(feature = "virt")]
+    fn to_datafusion(&self) -> anyhow::Result<datafusion::prelude::Expr> {
+        use datafusion::scalar::ScalarValue;
+        Ok(match self {
+            Operand::Column(name, _) => datafusion::prelude::ident(name),
+            Operand::Value(x) => datafusion::prelude::lit(ScalarValue::try_from_array(x, 0)?),
+        })
+    }
+}
+
+/// A filter expression which couldn't be parsed
+#[derive(Debug)]
+pub struct ParseError {
+    msg: String,
+    /// The bytes of the offending token
+    span: Range<usize>,
+}
+
+impl ParseError {
+    /// Underlines the offending token, eg:
+    ///
+    /// ```text
+    /// age > thirty
+    ///       ^^^^^^
+    /// ```
+    pub fn pointer(&self, txt: &str) -> String {
+        let indent = txt[..self.span.start].chars().count();
+        let len = txt[self.span.clone()].chars().count().max(1);
+        format!("{txt}\n{}{}", " ".repeat(indent), "^".repeat(len))
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.msg)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Clone, PartialEq)]
+enum Token {
+    /// A bare word: a column name or a keyword
+    Word(String),
+    /// A column name in double quotes or backticks
+    QuotedName(String),
+    /// A string literal in single quotes
+    Str(String),
+    Number(String),
+    Op(Op),
+    LParen,
+    RParen,
+    Comma,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Word(x) => write!(f, "`{x}`"),
+            Token::QuotedName(x) => write!(f, "`\"{x}\"`"),
+            Token::Str(x) => write!(f, "`'{x}'`"),
+            Token::Number(x) => write!(f, "`{x}`"),
+            Token::Op(_) => f.write_str("an operator"),
+            Token::LParen => f.write_str("`(`"),
+            Token::RParen => f.write_str("`)`"),
+            Token::Comma => f.write_str("`,`"),
+        }
+    }
+}
+
+fn tokenize(txt: &str) -> Result<Vec<(Token, Range<usize>)>, ParseError> {
+    let mut tokens = vec![];
+    let mut chars = txt.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let mut take_while = |pred: &dyn Fn(char) -> bool| {
+            let mut end = start + c.len_utf8();
+            while let Some((i, c)) = chars.peek().copied() {
+                if !pred(c) {
+                    break;
+                }
+                chars.next();
+                end = i + c.len_utf8();
+            }
+            end
+        };
+        let (token, end) = match c {
+            c if c.is_whitespace() => continue,
+            '(' => (Token::LParen, start + 1),
+            ')' => (Token::RParen, start + 1),
+            ',' => (Token::Comma, start + 1),
+            '=' | '!' | '<' | '>' => {
+                let end = take_while(&|c| matches!(c, '=' | '>'));
+                let op = match &txt[start..end] {
+                    "=" | "==" => Op::Eq,
+                    "!=" | "<>" => Op::NotEq,
+                    "<" => Op::Lt,
+                    "<=" => Op::LtEq,
+                    ">" => Op::Gt,
+                    ">=" => Op::GtEq,
+                    op => {
+                        return Err(ParseError {
+                            msg: format!("Invalid operator: {op}"),
+                            span: start..end,
+                        });
+                    }
+                };
+                (Token::Op(op), end)
+            }
+            '\'' | '"' | '`' => {
+                let (value, end) = quoted(txt, start, c)?;
+                // Skip the rest of the quoted token
+                while chars.peek().is_some_and(|(i, _)| *i < end) {
+                    chars.next();
+                }
+                let token = match c {
+                    '\'' => Token::Str(value),
+                    _ => Token::QuotedName(value),
+                };
+                (token, end)
+            }
+            c if c.is_ascii_digit() || c == '-' || c == '.' => {
+                // Unquoted dates and times are allowed, eg. 2024-01-31
+                let end =
+                    take_while(&|c| c.is_alphanumeric() || matches!(c, '.' | '-' | ':' | '+'));
+                (Token::Number(txt[start..end].to_owned()), end)
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let end = take_while(&|c| c.is_alphanumeric() || c == '_');
+                (Token::Word(txt[start..end].to_owned()), end)
+            }
+            c => {
+                return Err(ParseError {
+                    msg: format!("Unexpected character: {c}"),
+                    span: start..start + c.len_utf8(),
+                });
+            }
+        };
+        tokens.push((token, start..end));
+    }
+    Ok(tokens)
+}
+
+/// Reads a quoted token beginning at `start`.  The quote character can be
+/// escaped by doubling it.  Returns the unquoted value and the end of the
+/// token.
+fn quoted(txt: &str, start: usize, quote: char) -> Result<(String, usize), ParseError> {
+    let mut value = String::new();
+    let mut chars = txt[start + 1..].char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        if c != quote {
+            value.push(c);
+        } else if chars.peek().is_some_and(|(_, c)| *c == quote) {
+            value.push(c);
+            chars.next();
+        } else {
+            return Ok((value, start + 1 + i + 1));
+        }
+    }
+    Err(ParseError {
+        msg: format!("Unterminated {quote}"),
+        span: start..txt.len(),
+    })
+}
+
+struct Parser<'a> {
+    tokens: Vec<(Token, Range<usize>)>,
+    pos: usize,
+    /// The length of the expression
+    end: usize,
+    schema: &'a Schema,
+    /// The columns mentioned so far
+    columns: Vec<String>,
+}
+
+/// An operand whose type hasn't been determined yet
+enum RawOperand {
+    Column(String, DataType),
+    Literal(Literal, Range<usize>),
+}
+
+enum Literal {
+    Str(String),
+    Number(String),
+    Bool(bool),
+    Null,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|x| &x.0)
+    }
+
+    fn next(&mut self) -> Option<(Token, Range<usize>)> {
+        let x = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        x
+    }
+
+    /// Consumes the next token if it's the given keyword
+    fn keyword(&mut self, kw: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Word(x)) if x.eq_ignore_ascii_case(kw));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, token: Token, what: &str) -> Result<(), ParseError> {
+        if self.peek() == Some(&token) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.unexpected(what))
+        }
+    }
+
+    /// An error pointing at the current token
+    fn unexpected(&self, expected: &str) -> ParseError {
+        match self.tokens.get(self.pos) {
+            Some((token, span)) => ParseError {
+                msg: format!("Expected {expected}, found {token}"),
+                span: span.clone(),
+            },
+            None => ParseError {
+                msg: format!("Expected {expected}, found the end of the expression"),
+                span: self.end..self.end,
+            },
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.parse_and()?;
+        while self.keyword("or") {
+            lhs = Expr::Or(Box::new(lhs), Box::new(self.parse_and()?));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.parse_not()?;
+        while self.keyword("and") {
+            lhs = Expr::And(Box::new(lhs), Box::new(self.parse_not()?));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, ParseError> {
+        if self.keyword("not") {
+            Ok(Expr::Not(Box::new(self.parse_not()?)))
+        } else {
+            self.parse_predicate()
+        }
+    }
+
+    fn parse_predicate(&mut self) -> Result<Expr, ParseError> {
+        if self.peek() == Some(&Token::LParen) {
+            self.pos += 1;
+            let expr = self.parse_or()?;
+            self.expect(Token::RParen, "`)`")?;
+            return Ok(expr);
+        }
+        let lhs = self.parse_operand()?;
+        if let Some(Token::Op(op)) = self.peek().cloned() {
+            self.pos += 1;
+            let rhs = self.parse_operand()?;
+            // Literals take the type of the column they're compared with
+            let (lhs, rhs) = match (lhs, rhs) {
+                (lhs @ RawOperand::Column(..), rhs) => {
+                    let lhs = self.resolve(lhs, None)?;
+                    let rhs = self.resolve(rhs, Some(lhs.data_type()))?;
+                    (lhs, rhs)
+                }
+                (lhs, rhs) => {
+                    let rhs = self.resolve(rhs, None)?;
+                    let lhs = self.resolve(lhs, Some(rhs.data_type()))?;
+                    (lhs, rhs)
+                }
+            };
+            return Ok(Expr::Cmp(lhs, op, rhs));
+        }
+        if self.keyword("is") {
+            let negated = self.keyword("not");
+            if !self.keyword("null") {
+                return Err(self.unexpected("`NULL`"));
+            }
+            return Ok(Expr::IsNull(self.resolve(lhs, None)?, negated));
+        }
+        let negated = self.keyword("not");
+        if self.keyword("in") {
+            let lhs = self.resolve(lhs, None)?;
+            self.expect(Token::LParen, "`(`")?;
+            let mut list = vec![];
+            loop {
+                let item = self.parse_operand()?;
+                list.push(self.resolve(item, Some(lhs.data_type()))?);
+                if self.peek() == Some(&Token::Comma) {
+                    self.pos += 1;
+                } else {
+                    break;
+                }
+            }
+            self.expect(Token::RParen, "`,` or `)`")?;
+            return Ok(Expr::InList(lhs, list, negated));
+        }
+        let case_insensitive = if self.keyword("like") {
+            false
+        } else if self.keyword("ilike") {
+            true
+        } else if negated {
+            return Err(self.unexpected("`IN`, `LIKE`, or `ILIKE`"));
+        } else if let RawOperand::Column(name, DataType::Boolean) = lhs {
+            // A boolean column on its own
+            let value = Operand::Value(Arc::new(BooleanArray::from(vec![true])));
+            return Ok(Expr::Cmp(
+                Operand::Column(name, DataType::Boolean),
+                Op::Eq,
+                value,
+            ));
+        } else {
+            return Err(self.unexpected("a comparison"));
+        };
+        let value = match lhs {
+            RawOperand::Column(name, _) => Operand::Column(name, DataType::Utf8),
+            lhs => self.resolve(lhs, Some(&DataType::Utf8))?,
+        };
+        match self.next() {
+            Some((Token::Str(pattern), _)) => Ok(Expr::Like {
+                value,
+                pattern,
+                case_insensitive,
+                negated,
+            }),
+            _ => {
+                self.pos -= 1;
+                Err(self.unexpected("a pattern in single quotes"))
+            }
+        }
+    }
+
+    fn parse_operand(&mut self) -> Result<RawOperand, ParseError> {
+        let Some((token, span)) = self.next() else {
+            return Err(self.unexpected("a column or a value"));
+        };
+        let literal = |x| Ok(RawOperand::Literal(x, span.clone()));
+        let bare = matches!(token, Token::Word(_));
+        match token {
+            Token::Word(x) if x.eq_ignore_ascii_case("true") => literal(Literal::Bool(true)),
+            Token::Word(x) if x.eq_ignore_ascii_case("false") => literal(Literal::Bool(false)),
+            Token::Word(x) if x.eq_ignore_ascii_case("null") => literal(Literal::Null),
+            Token::Word(name) | Token::QuotedName(name) => {
+                let Ok(field) = self.schema.field_with_name(&name) else {
+                    let hint = if bare {
+                        " (put strings in single quotes)"
+                    } else {
+                        ""
+                    };
+                    return Err(ParseError {
+                        msg: format!("No such column: {name}{hint}"),
+                        span,
+                    });
+                };
+                if !self.columns.contains(&name) {
+                    self.columns.push(name.clone());
+                }
+                Ok(RawOperand::Column(name, field.data_type().clone()))
+            }
+            Token::Str(x) => literal(Literal::Str(x)),
+            Token::Number(x) => literal(Literal::Number(x)),
+            _ => {
+                self.pos -= 1;
+                Err(self.unexpected("a column or a value"))
+            }
+        }
+    }
+
+    /// Determines the type of the operand.  If `dtype` is given, the operand
+    /// is converted to it.
+    fn resolve(&self, x: RawOperand, dtype: Option<&DataType>) -> Result<Operand, ParseError> {
+        let (literal, span) = match x {
+            RawOperand::Column(name, own) => {
+                return Ok(Operand::Column(name, dtype.cloned().unwrap_or(own)));
+            }
+            RawOperand::Literal(x, span) => (x, span),
+        };
+        let array: ArrayRef = match (literal, dtype) {
+            (Literal::Null, dtype) => {
+                arrow::array::new_null_array(dtype.unwrap_or(&DataType::Null), 1)
+            }
+            (Literal::Bool(x), None | Some(DataType::Boolean)) => {
+                Arc::new(BooleanArray::from(vec![x]))
+            }
+            (Literal::Bool(x), Some(dtype)) => parse_value(&x.to_string(), dtype, span)?,
+            (Literal::Str(x), dtype) => parse_value(&x, dtype.unwrap_or(&DataType::Utf8), span)?,
+            (Literal::Number(x), Some(dtype)) => parse_value(&x, dtype, span)?,
+            (Literal::Number(x), None) if x.parse::<i64>().is_ok() => {
+                parse_value(&x, &DataType::Int64, span)?
+            }
+            (Literal::Number(x), None) => parse_value(&x, &DataType::Float64, span)?,
+        };
+        Ok(Operand::Value(array))
+    }
+}
+
+/// Converts the literal to a single-element array of the given type
+fn parse_value(txt: &str, dtype: &DataType, span: Range<usize>) -> Result<ArrayRef, ParseError> {
+    let array = Arc::new(StringArray::from(vec![txt])) as ArrayRef;
+    let opts = CastOptions {
+        safe: false,
+        ..Default::default()
+    };
+    cast_with_options(&array, dtype, &opts).map_err(|_| ParseError {
+        msg: format!("Not a valid {dtype}: {txt}"),
+        span,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{BinaryArray, Float64Array, Int64Array};
+    use arrow::datatypes::Field;
+
+    #[test]
+    fn boolean_expressions() -> anyhow::Result<()> {
+        let batch = RecordBatch::try_from_iter([
+            (
+                "status",
+                Arc::new(StringArray::from(vec![
+                    Some("ok"),
+                    Some("error"),
+                    None,
+                    Some("error"),
+                ])) as ArrayRef,
+            ),
+            (
+                "latency",
+                Arc::new(Int64Array::from(vec![500, 50, 200, 150])) as ArrayRef,
+            ),
+            (
+                "retry count",
+                Arc::new(Int64Array::from(vec![Some(0), Some(5), Some(1), None])) as ArrayRef,
+            ),
+        ])?;
+        let eval = |txt: &str| -> anyhow::Result<Vec<bool>> {
+            let mask = Filter::parse(txt, &batch.schema())?.evaluate(&batch)?;
+            Ok(mask.iter().map(|x| x.unwrap()).collect())
+        };
+        assert_eq!(
+            eval("status != 'ok' and (latency > 100 or \"retry count\" >= 3)")?,
+            [false, true, false, true]
+        );
+        assert_eq!(eval("status is null")?, [false, false, true, false]);
+        assert_eq!(
+            eval("status in ('ok', 'warn')")?,
+            [true, false, false, false]
+        );
+        assert_eq!(eval("not status like 'err%'")?, [true, false, false, false]);
+        assert_eq!(eval("latency >= 150 and 1 = 1")?, [true, false, true, true]);
+        Ok(())
+    }
+
+    #[test]
+    fn equals_exact_values() -> anyhow::Result<()> {
+        let x = 0.1 + 0.2;
+        let batch = RecordBatch::try_from_iter([
+            (
+                "x",
+                Arc::new(Float64Array::from(vec![Some(x), Some(0.3), None])) as ArrayRef,
+            ),
+            (
+                "y",
+                Arc::new(BinaryArray::from(vec![Some(&b"\xff"[..]), None, Some(b"")])) as ArrayRef,
+            ),
+        ])?;
+        let eval = |column: &str, row: usize| -> anyhow::Result<Vec<bool>> {
+            let value = batch.column_by_name(column).unwrap().slice(row, 1);
+            let mask = Filter::equals(column, &value)?.evaluate(&batch)?;
+            Ok(mask.iter().map(|x| x.unwrap()).collect())
+        };
+        assert_eq!(eval("x", 0)?, [true, false, false]);
+        assert_eq!(eval("x", 2)?, [false, false, true]);
+        assert_eq!(eval("y", 0)?, [true, false, false]);
+        Ok(())
+    }
+
+    #[test]
+    fn errors_point_at_the_token() {
+        let schema = Schema::new(vec![Field::new("age", DataType::Int64, true)]);
+        let err = |txt| Filter::parse(txt, &schema).err().unwrap().pointer(txt);
+        assert_eq!(err("age > thirty"), "age > thirty\n      ^^^^^^");
+        assert_eq!(err("age > 1x"), "age > 1x\n      ^^");
+        assert_eq!(err("(age > 3"), "(age > 3\n        ^");
+        assert_eq!(err("age = 'x"), "age = 'x\n      ^^");
+    }
+}
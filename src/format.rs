@@ -0,0 +1,166 @@
+use crate::draw::RenderSettings;
+use arrow::{
+    array::{Array, AsArray},
+    datatypes::*,
+    temporal_conversions,
+};
+use chrono::TimeZone;
+use chrono_tz::Tz;
+use std::fmt::{Display, Write};
+
+/// Formats individual cells of a column, the same way they're rendered in the
+/// grid
+pub struct CellFormatter<'a> {
+    col: &'a dyn Array,
+    fmt: FmtFn<'a>,
+}
+
+type FmtFn<'a> = Box<dyn Fn(&mut String, usize) -> std::fmt::Result + 'a>;
+
+impl<'a> CellFormatter<'a> {
+    pub fn new(col: &'a dyn Array, settings: &RenderSettings) -> anyhow::Result<Self> {
+        let dps = settings.float_dps;
+        let fmt: FmtFn<'a> = match col.data_type() {
+            DataType::Null => Box::new(|_, _| Ok(())),
+            DataType::Boolean => {
+                let col = col.as_boolean();
+                Box::new(move |buf, row| fmt_display(buf, col.value(row)))
+            }
+
+            DataType::Int8 => primitive::<Int8Type>(col, fmt_display),
+            DataType::Int16 => primitive::<Int16Type>(col, fmt_display),
+            DataType::Int32 => primitive::<Int32Type>(col, fmt_display),
+            DataType::Int64 => primitive::<Int64Type>(col, fmt_display),
+            DataType::UInt8 => primitive::<UInt8Type>(col, fmt_display),
+            DataType::UInt16 => primitive::<UInt16Type>(col, fmt_display),
+            DataType::UInt32 => primitive::<UInt32Type>(col, fmt_display),
+            DataType::UInt64 => primitive::<UInt64Type>(col, fmt_display),
+            DataType::Float16 => {
+                primitive::<Float16Type>(col, move |buf, x| fmt_float(buf, x, dps))
+            }
+            DataType::Float32 => {
+                primitive::<Float32Type>(col, move |buf, x| fmt_float(buf, x, dps))
+            }
+            DataType::Float64 => {
+                primitive::<Float64Type>(col, move |buf, x| fmt_float(buf, x, dps))
+            }
+
+            DataType::Timestamp(unit, tz) => {
+                let tz = parse_tz(tz.as_deref());
+                match unit {
+                    TimeUnit::Second => timestamp::<TimestampSecondType>(col, tz),
+                    TimeUnit::Millisecond => timestamp::<TimestampMillisecondType>(col, tz),
+                    TimeUnit::Microsecond => timestamp::<TimestampMicrosecondType>(col, tz),
+                    TimeUnit::Nanosecond => timestamp::<TimestampNanosecondType>(col, tz),
+                }
+            }
+            DataType::Date32 => {
+                primitive::<Date32Type>(col, |buf, x| fmt_date::<Date32Type>(buf, x.into()))
+            }
+            DataType::Date64 => primitive::<Date64Type>(col, fmt_date::<Date64Type>),
+            DataType::Time32(TimeUnit::Second) => primitive::<Time32SecondType>(col, |buf, x| {
+                fmt_time::<Time32SecondType>(buf, x.into())
+            }),
+            DataType::Time32(TimeUnit::Millisecond) => {
+                primitive::<Time32MillisecondType>(col, |buf, x| {
+                    fmt_time::<Time32MillisecondType>(buf, x.into())
+                })
+            }
+            DataType::Time64(TimeUnit::Microsecond) => {
+                primitive::<Time64MicrosecondType>(col, fmt_time::<Time64MicrosecondType>)
+            }
+            DataType::Time64(TimeUnit::Nanosecond) => {
+                primitive::<Time64NanosecondType>(col, fmt_time::<Time64NanosecondType>)
+            }
+
+            DataType::Utf8 => {
+                let col = col.as_string::<i32>();
+                Box::new(move |buf, row| buf.write_str(col.value(row)))
+            }
+            DataType::LargeUtf8 => {
+                let col = col.as_string::<i64>();
+                Box::new(move |buf, row| buf.write_str(col.value(row)))
+            }
+            DataType::Binary => {
+                let col = col.as_binary::<i32>();
+                Box::new(move |buf, row| fmt_binary(buf, col.value(row)))
+            }
+            DataType::LargeBinary => {
+                let col = col.as_binary::<i64>();
+                Box::new(move |buf, row| fmt_binary(buf, col.value(row)))
+            }
+
+            _ => {
+                use arrow::util::display::*;
+                let options = FormatOptions::default();
+                let formatter = ArrayFormatter::try_new(col, &options)?;
+                Box::new(move |buf, row| write!(buf, "{}", formatter.value(row)))
+            }
+        };
+        Ok(CellFormatter { col, fmt })
+    }
+
+    /// Appends the formatted value to `buf`.  Nulls are formatted as the empty
+    /// string.
+    pub fn write(&self, buf: &mut String, row: usize) -> std::fmt::Result {
+        if self.col.is_null(row) {
+            return Ok(());
+        }
+        (self.fmt)(buf, row)
+    }
+}
+
+fn primitive<'a, T: ArrowPrimitiveType>(
+    col: &'a dyn Array,
+    f: impl Fn(&mut String, T::Native) -> std::fmt::Result + 'a,
+) -> FmtFn<'a> {
+    let col = col.as_primitive::<T>();
+    Box::new(move |buf, row| f(buf, col.value(row)))
+}
+
+fn timestamp<'a, T: ArrowPrimitiveType<Native = i64>>(
+    col: &'a dyn Array,
+    tz: Option<Tz>,
+) -> FmtFn<'a> {
+    primitive::<T>(col, move |buf, x| fmt_timestamp::<T>(buf, x, tz))
+}
+
+pub fn parse_tz(tz: Option<&str>) -> Option<Tz> {
+    tz.map(|tz| tz.parse().unwrap())
+}
+
+pub fn fmt_display(buf: &mut String, val: impl Display) -> std::fmt::Result {
+    write!(buf, "{val}")
+}
+
+pub fn fmt_float(buf: &mut String, val: impl Display, dps: usize) -> std::fmt::Result {
+    write!(buf, "{val:.dps$}")
+}
+
+pub fn fmt_binary(buf: &mut String, val: &[u8]) -> std::fmt::Result {
+    write!(buf, "{}", val.escape_ascii())
+}
+
+pub fn fmt_timestamp<T: ArrowPrimitiveType>(
+    buf: &mut String,
+    val: i64,
+    tz: Option<Tz>,
+) -> std::fmt::Result {
+    let datetime = temporal_conversions::as_datetime::<T>(val).unwrap();
+    if let Some(tz) = tz {
+        let datetime = tz.from_utc_datetime(&datetime);
+        write!(buf, "{datetime}")
+    } else {
+        write!(buf, "{datetime}")
+    }
+}
+
+pub fn fmt_date<T: ArrowPrimitiveType>(buf: &mut String, val: i64) -> std::fmt::Result {
+    let date = temporal_conversions::as_date::<T>(val).unwrap();
+    write!(buf, "{date}")
+}
+
+pub fn fmt_time<T: ArrowPrimitiveType>(buf: &mut String, val: i64) -> std::fmt::Result {
+    let time = temporal_conversions::as_time::<T>(val).unwrap();
+    write!(buf, "{time}")
+}
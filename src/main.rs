@@ -1,35 +1,52 @@
 mod backend;
+mod decompress;
 mod draw;
+mod filter;
+mod format;
 mod prompt;
+mod search;
+mod sort;
 mod stats;
+mod summary;
+mod view;
 
-use crate::backend::DataSource;
+use crate::backend::freq::FreqJob;
+use crate::backend::multi::{MultiSource, Part};
+use crate::backend::{DataSource, conform};
 use crate::draw::*;
+use crate::filter::Filter;
 use crate::prompt::*;
+use crate::search::{CaseMode, Needle, Search, SearchOpts};
+use crate::sort::Permutation;
 use crate::stats::*;
+use crate::summary::ColumnSummary;
+use crate::view::RowMap;
 use anyhow::Context;
 use anyhow::bail;
-#[cfg(feature = "virt")]
 use anyhow::ensure;
-use arrow::datatypes::Schema;
+use arrow::array::{Array, ArrayRef, BooleanArray, BooleanBufferBuilder};
+use arrow::buffer::BooleanBuffer;
+use arrow::datatypes::{DataType, Schema};
 use arrow::record_batch::RecordBatch;
 use bpaf::{Bpaf, Parser};
 use crossterm::tty::IsTty;
 use crossterm::*;
 use std::cmp::Ordering;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::BufWriter;
-use std::io::{LineWriter, Write};
+use std::io::{LineWriter, Read, Seek, Write};
 use std::ops::Range;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tracing::{debug, warn};
+use tracing::{debug, error, warn};
 
 /// A pager for tabular data
 ///
-/// Data can be in CSV or parquet format.  The format is inferred from the file
+/// Data can be in CSV, JSON, parquet, or Arrow IPC format.  The format is inferred from the file
 /// extension.  When data is read from stdin, it is expected to be CSV.
+/// Compressed data (gzip, zstd, bzip2, or xz) is decompressed automatically.
 #[derive(Bpaf)]
 struct Opts {
     /// How many decimal places to show when rendering floating-point numbers
@@ -37,20 +54,41 @@ struct Opts {
     precision: usize,
     /// Whether to hide empty columns
     hide_empty: bool,
+    /// Show a histogram of each column above its name
+    histograms: bool,
     /// The format of the data.  Inferred from the file extension if unspecified
     #[bpaf(long("format"), short('f'))]
     format: Option<String>,
     /// A column to sort by. Prefix with '-' to invert
     #[cfg(feature = "virt")]
     sort: Vec<String>,
-    /// A filter expression, eg. 'age > 30'
+    /// A filter expression, eg. "status != 'ok' and latency > 100"
     #[cfg(feature = "virt")]
     filter: Vec<String>,
+    /// An SQL query to run against the file, which is available as table `t`
+    #[cfg(feature = "virt")]
+    sql: Option<String>,
     /// Move this column to the left
     column: Vec<String>,
-    /// The path to read.  If not specified, data will be read from stdin
-    #[bpaf(positional)]
-    path: Option<PathBuf>,
+    /// Ignore case when searching, unless the pattern contains uppercase letters
+    #[bpaf(short('i'), long("smart-case"))]
+    smart_case: bool,
+    /// Ignore case when searching
+    #[bpaf(short('I'), long("ignore-case"))]
+    ignore_case: bool,
+    /// Interpret search patterns as regular expressions
+    #[bpaf(short('r'), long("regex"))]
+    regex: bool,
+    /// Add a `_file` column showing which file each row came from
+    file_column: bool,
+    /// Open each path in its own tab, rather than concatenating them
+    #[bpaf(short('t'), long("tabs"))]
+    tabs: bool,
+    /// The paths to read.  Globs are expanded, and directories are read as
+    /// hive-partitioned parquet datasets.  If not specified, data will be read
+    /// from stdin
+    #[bpaf(positional("PATH"))]
+    paths: Vec<PathBuf>,
 }
 
 fn main() {
@@ -90,77 +128,439 @@ impl Drop for RawTermGuard {
     }
 }
 
-fn run(opts: Opts) -> anyhow::Result<()> {
+fn run(mut opts: Opts) -> anyhow::Result<()> {
+    opts.paths = expand_globs(&opts.paths)?;
+
     let guard = setup_term()?;
 
     let settings = RenderSettings {
         float_dps: opts.precision,
         hide_empty: opts.hide_empty,
+        histograms: opts.histograms,
+    };
+
+    let search_opts = SearchOpts {
+        regex: opts.regex,
+        case: if opts.ignore_case {
+            CaseMode::Insensitive
+        } else if opts.smart_case {
+            CaseMode::Smart
+        } else {
+            CaseMode::Sensitive
+        },
     };
 
-    let source = CachedSource::new(get_source(&opts)?, opts.column);
+    let tab_paths = if opts.tabs && !opts.paths.is_empty() {
+        opts.paths.iter().map(|x| vec![x.clone()]).collect()
+    } else {
+        vec![opts.paths.clone()]
+    };
+    let tabs = tab_paths
+        .into_iter()
+        .map(|paths| {
+            let source = get_source(&opts, &paths, &settings)?;
+            Ok(Tab::new(
+                paths,
+                CachedSource::new(source, opts.column.clone()),
+            ))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
 
     let stdout = std::io::stdout();
     let mut stdout = BufWriter::new(stdout.lock());
 
-    runloop(&mut stdout, source, settings)?;
+    // Used by the `:sql` command
+    let run_query = |paths: &[PathBuf], sql: &str| query(&opts, paths, sql, &settings);
+
+    runloop(&mut stdout, tabs, settings.clone(), search_opts, run_query)?;
 
     std::mem::drop(guard);
     Ok(())
 }
 
-fn get_source(opts: &Opts) -> anyhow::Result<Box<dyn DataSource>> {
-    #[cfg(feature = "virt")]
-    if !opts.sort.is_empty() || !opts.filter.is_empty() {
-        let Some(path) = &opts.path else {
-            bail!("Can't filter streaming data")
+/// Expands any paths which contain glob characters.  Matches are sorted.
+fn expand_globs(paths: &[PathBuf]) -> anyhow::Result<Vec<PathBuf>> {
+    let mut expanded = vec![];
+    for path in paths {
+        let Some(pattern) = path.to_str().filter(|x| x.contains(['*', '?', '['])) else {
+            expanded.push(path.clone());
+            continue;
         };
-        let ext = path.extension().and_then(|x| x.to_str());
-        ensure!(ext == Some("parquet"), "Can't filter this file type");
-        let source = crate::backend::virt::VirtualFile::new(path, &opts.sort, &opts.filter)?;
-        return Ok(Box::new(source));
+        let mut matches = glob::glob(pattern)?.collect::<Result<Vec<_>, _>>()?;
+        ensure!(!matches.is_empty(), "No files match {pattern}");
+        matches.sort();
+        expanded.extend(matches);
     }
+    Ok(expanded)
+}
 
-    let (file, ext) = match &opts.path {
-        Some(x) => (File::open(x)?, x.extension().and_then(|x| x.to_str())),
+/// Loads the data into DataFusion, which runs the query (if any) and applies
+/// the sort order and filters.  The data is available as table `t`.
+/// Streaming data and compressed files are copied into a tempfile first.
+#[cfg(feature = "virt")]
+fn virtual_file(
+    opts: &Opts,
+    paths: &[PathBuf],
+    sql: Option<&str>,
+    sort: &[String],
+    filter: &[String],
+    settings: &RenderSettings,
+) -> anyhow::Result<Box<dyn DataSource>> {
+    use crate::backend::virt::{Table, VirtualFile};
+    let table = match paths {
+        [] => {
+            let stdin = std::io::stdin();
+            if stdin.is_tty() {
+                bail!("Need to specify a filename or feed data to stdin");
+            }
+            let mut table = Table::new(opts.format.as_deref().unwrap_or("csv"));
+            table.add_snapshot(stdin)?;
+            table
+        }
+        paths => {
+            // All the files are read with the same format, which is taken from
+            // the first one.  Directories are assumed to contain parquet.  Like
+            // the native backends, we assume CSV if we can't tell.
+            let ext = if paths[0].is_dir() {
+                Some("parquet")
+            } else {
+                extension(&paths[0]).0
+            };
+            let format = opts.format.as_deref().or(ext).unwrap_or("csv");
+            let mut table = Table::new(format);
+            for path in paths {
+                if path.is_dir() {
+                    let files = crate::backend::hive::discover(path)?;
+                    let keys = files[0].keys.iter().map(|(k, _)| k.clone()).collect();
+                    table.add_dir(path.clone(), keys);
+                } else if extension(path).1 {
+                    table.add_snapshot(File::open(path)?)?;
+                } else {
+                    table.add_file(path.clone());
+                }
+            }
+            table
+        }
+    };
+    Ok(Box::new(VirtualFile::new(
+        table, sql, sort, filter, settings,
+    )?))
+}
+
+/// Runs the query against the file, which is available as table `t`
+#[cfg(feature = "virt")]
+fn query(
+    opts: &Opts,
+    paths: &[PathBuf],
+    sql: &str,
+    settings: &RenderSettings,
+) -> anyhow::Result<Box<dyn DataSource>> {
+    // By now, stdin has already been consumed
+    ensure!(!paths.is_empty(), "Can't query streaming data");
+    virtual_file(opts, paths, Some(sql), &[], &[], settings)
+}
+
+#[cfg(not(feature = "virt"))]
+fn query(
+    _opts: &Opts,
+    _paths: &[PathBuf],
+    _sql: &str,
+    _settings: &RenderSettings,
+) -> anyhow::Result<Box<dyn DataSource>> {
+    bail!("tass was built without SQL support (the \"virt\" feature)")
+}
+
+fn get_source(
+    opts: &Opts,
+    paths: &[PathBuf],
+    settings: &RenderSettings,
+) -> anyhow::Result<Box<dyn DataSource>> {
+    #[cfg(feature = "virt")]
+    if opts.sql.is_some() || !opts.sort.is_empty() || !opts.filter.is_empty() {
+        return virtual_file(
+            opts,
+            paths,
+            opts.sql.as_deref(),
+            &opts.sort,
+            &opts.filter,
+            settings,
+        );
+    }
+
+    let format = opts.format.as_deref();
+    match paths {
+        [] => open_source(None, format, settings),
+        [path] if !opts.file_column && !path.is_dir() => open_source(Some(path), format, settings),
+        paths => {
+            let mut parts = vec![];
+            for path in paths {
+                parts.extend(open_parts(path, format, settings)?);
+            }
+            Ok(Box::new(MultiSource::new(parts, opts.file_column)?))
+        }
+    }
+}
+
+/// Opens a file, or all the files in a hive-partitioned directory
+fn open_parts(
+    path: &Path,
+    format: Option<&str>,
+    settings: &RenderSettings,
+) -> anyhow::Result<Vec<Part>> {
+    if path.is_dir() {
+        #[cfg(not(feature = "parquet"))]
+        bail!("{} is a directory", path.display());
+        #[cfg(feature = "parquet")]
+        return crate::backend::hive::discover(path)?
+            .into_iter()
+            .map(|file| {
+                let source = open_source(Some(&file.path), format, settings)?;
+                Ok(Part::new(file.path.display().to_string(), source).with_keys(file.keys))
+            })
+            .collect();
+    }
+    let source = open_source(Some(path), format, settings)?;
+    Ok(vec![Part::new(path.display().to_string(), source)])
+}
+
+/// Opens a single file, or stdin if `path` is `None`
+fn open_source(
+    path: Option<&Path>,
+    format: Option<&str>,
+    settings: &RenderSettings,
+) -> anyhow::Result<Box<dyn DataSource>> {
+    let (file, ext) = match path {
+        Some(x) => open_path(x, format)?,
         None => {
-            let mut stdin = std::io::stdin();
+            let stdin = std::io::stdin();
             if stdin.is_tty() {
                 bail!("Need to specify a filename or feed data to stdin");
             }
-            let tmpfile = tempfile::tempfile()?;
-            let mut wtr = LineWriter::new(tmpfile.try_clone()?);
-            std::thread::spawn(move || std::io::copy(&mut stdin, &mut wtr));
-            (tmpfile, None)
+            (spool(stdin)?, None)
         }
     };
 
-    Ok(match opts.format.as_deref().or(ext) {
+    Ok(match format.or(ext) {
         #[cfg(feature = "parquet")]
-        Some("parquet") => Box::new(crate::backend::parquet::ParquetFile::new(file)?),
+        Some("parquet") => Box::new(crate::backend::parquet::ParquetFile::new(file, settings)?),
+        #[cfg(feature = "ipc")]
+        Some("arrow" | "feather" | "ipc") => {
+            Box::new(crate::backend::ipc::IpcFile::new(file, settings)?)
+        }
+        #[cfg(feature = "ipc")]
+        Some("arrows") => Box::new(crate::backend::ipc::IpcStream::new(file, settings)?),
         #[cfg(feature = "csv")]
-        Some("csv") => Box::new(crate::backend::csv::CsvFile::new(file, b',')?),
+        Some("csv") => Box::new(crate::backend::csv::CsvFile::new(file, b',', settings)?),
         #[cfg(feature = "csv")]
-        Some("tsv") => Box::new(crate::backend::csv::CsvFile::new(file, b'\t')?),
+        Some("tsv") => Box::new(crate::backend::csv::CsvFile::new(file, b'\t', settings)?),
         #[cfg(feature = "json")]
-        Some("json" | "jsonl" | "ndjson") => Box::new(crate::backend::json::JsonFile::new(file)?),
+        Some("json" | "jsonl" | "ndjson") => {
+            Box::new(crate::backend::json::JsonFile::new(file, settings)?)
+        }
         #[cfg(feature = "csv")]
-        None => Box::new(crate::backend::csv::CsvFile::new(file, b',')?),
+        None => Box::new(crate::backend::csv::CsvFile::new(file, b',', settings)?),
         _ => bail!("Unrecognised file extension"),
     })
 }
 
+/// Opens the file, and returns it along with its extension.  Compressed files
+/// are decompressed into a tempfile first.  Formats which can't be read until
+/// they're complete (ie. those with a footer) are decompressed up-front;
+/// others are decompressed in the background.
+fn open_path<'a>(path: &'a Path, format: Option<&str>) -> anyhow::Result<(File, Option<&'a str>)> {
+    let file = File::open(path)?;
+    let (ext, compressed) = extension(path);
+    if compressed {
+        let file = match format.or(ext) {
+            Some("parquet" | "arrow" | "feather" | "ipc") => decompress(file)?,
+            _ => spool(file)?,
+        };
+        return Ok((file, ext));
+    }
+    Ok((file, ext))
+}
+
+/// The file's extension, and whether it's compressed.  For compressed files,
+/// this is the extension before the compression suffix (eg. "csv" for
+/// "data.csv.gz").
+fn extension(path: &Path) -> (Option<&str>, bool) {
+    let ext = path.extension().and_then(|x| x.to_str());
+    if ext.is_some_and(crate::decompress::is_compressed_extension) {
+        let inner_ext = path
+            .file_stem()
+            .map(Path::new)
+            .and_then(|x| x.extension())
+            .and_then(|x| x.to_str());
+        return (inner_ext, true);
+    }
+    (ext, false)
+}
+
+/// Copies the data into a tempfile in a background thread, decompressing it
+/// if necessary.  The tempfile is returned immediately, and grows as the data
+/// comes in.
+fn spool(rdr: impl Read + Send + 'static) -> anyhow::Result<File> {
+    let tmpfile = tempfile::tempfile()?;
+    let mut wtr = LineWriter::new(tmpfile.try_clone()?);
+    std::thread::spawn(move || {
+        let result = crate::decompress::decompressed(rdr)
+            .and_then(|mut rdr| std::io::copy(&mut rdr, &mut wtr));
+        match result {
+            Ok(n_bytes) => debug!("Spooled {n_bytes} bytes"),
+            Err(e) => {
+                error!("Couldn't read the input: {e}");
+                *SPOOL_ERROR.lock().unwrap() = Some(e.to_string());
+            }
+        }
+    });
+    Ok(tmpfile)
+}
+
+/// Set when a spool thread fails, so that the runloop can report it
+static SPOOL_ERROR: std::sync::Mutex<Option<String>> = std::sync::Mutex::new(None);
+
+/// Copies the data into a tempfile, decompressing it if necessary.  This
+/// blocks until all the data has been read.
+fn decompress(rdr: impl Read + Send) -> anyhow::Result<File> {
+    let start = Instant::now();
+    let mut tmpfile = tempfile::tempfile()?;
+    let mut rdr = crate::decompress::decompressed(rdr)?;
+    let n_bytes = std::io::copy(&mut rdr, &mut tmpfile)?;
+    tmpfile.rewind()?;
+    debug!("Decompressed {n_bytes} bytes (took {:?})", start.elapsed());
+    Ok(tmpfile)
+}
+
 const CHUNK_SIZE: usize = 10_000;
+/// In a sorted or filtered view, neighbouring rows are scattered across the
+/// file, so we load fewer of them at a time
+const VIEW_CHUNK_SIZE: usize = 200;
+/// When loading rows for a sorted or filtered view, we read the source rows in
+/// runs.  Rows which are less than this far apart go in the same run.
+const MAX_RUN_GAP: usize = 32;
+
+/// A file (or set of files) with its own view state
+struct Tab {
+    /// Shown in the footer when there's more than one tab
+    name: String,
+    /// Where the data came from.  Empty for stdin.
+    paths: Vec<PathBuf>,
+    source: CachedSource,
+    /// The first row and column in the viewport
+    start_row: usize,
+    start_col: usize,
+    /// The selected row and column.  The viewport follows the cursor.
+    cursor_row: usize,
+    cursor_col: usize,
+    total_rows: usize,
+    highlights: HashSet<usize>,
+    search: Option<Search>,
+    /// Whether we should jump to the first match once the search finds it
+    jump_pending: bool,
+    /// Whether records are shown as columns, rather than rows
+    transposed: bool,
+    /// If this is a frequency table: the sheet it was opened from, and the
+    /// column it counts
+    parent: Option<(Box<Tab>, String)>,
+    /// Whether the data was computed by tass, rather than read from `paths`.
+    /// Such tabs can't be queried.
+    derived: bool,
+    /// A frequency table which is being built.  It's opened when it's ready.
+    freq_job: Option<FreqJob>,
+}
+
+impl Tab {
+    fn new(paths: Vec<PathBuf>, source: CachedSource) -> Tab {
+        let name = match paths.as_slice() {
+            [] => "stdin".to_owned(),
+            [path] => path.display().to_string(),
+            paths => format!("{} files", paths.len()),
+        };
+        Tab {
+            name,
+            paths,
+            source,
+            start_row: 0,
+            start_col: 0,
+            cursor_row: 0,
+            cursor_col: 0,
+            total_rows: 0,
+            highlights: HashSet::default(),
+            search: None,
+            jump_pending: false,
+            transposed: false,
+            parent: None,
+            derived: false,
+            freq_job: None,
+        }
+    }
+
+    /// The text shown at the bottom-right of the screen
+    fn status(&self, idx: usize, n_tabs: usize) -> String {
+        [
+            if n_tabs > 1 {
+                Some(format!("[{}/{n_tabs}] {}", idx + 1, self.name))
+            } else {
+                self.parent.as_ref().map(|_| self.name.clone())
+            },
+            self.source.filter_status(),
+            self.search.as_ref().map(Search::status),
+            self.freq_job.as_ref().map(FreqJob::status),
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+    }
+}
+
+/// The state of the record detail view
+struct Detail {
+    /// Every column of the row, including hidden ones
+    record: RecordBatch,
+    /// The row's position in the view
+    row: usize,
+    /// How many lines have been scrolled past
+    scroll: usize,
+}
 
 struct CachedSource {
     rearranged_columns: Vec<String>,
     inner: Box<dyn DataSource>,
     all_col_stats: Vec<ColumnStats>, // One per column
+    /// The source rows which have been included in `all_col_stats`
+    counted_rows: BooleanBufferBuilder,
     // The below refer to the loaded record batch
     big_df: RecordBatch,
     available_cols: Vec<usize>,   // The columns in big_df
     available_rows: Range<usize>, // The rows in big_df
     col_stats: Vec<ColumnStats>,  // One per column in big_df
+    /// If set, rows are shown in this order, rather than the source's order
+    sort: Option<SortOrder>,
+    /// Only rows which pass all of these are shown
+    filters: Vec<ActiveFilter>,
+    /// Which source rows are shown, if the view is sorted or filtered
+    view: Option<Arc<RowMap>>,
+    /// Columns which the user has explicitly shown or hidden.  Others are
+    /// shown unless they're empty and `hide_empty` is set.
+    col_visibility: HashMap<String, bool>,
+    /// Columns which stay on the left when scrolling horizontally
+    pinned_columns: Vec<String>,
+    /// How many of `available_cols` are pinned.  They come first.
+    n_pinned: usize,
+}
+
+struct SortOrder {
+    column: String,
+    descending: bool,
+    perm: Arc<Permutation>,
+}
+
+struct ActiveFilter {
+    /// Which source rows pass the filter.  Rows which were added after the
+    /// filter was applied don't.
+    mask: BooleanBuffer,
 }
 
 impl CachedSource {
@@ -169,13 +569,225 @@ impl CachedSource {
             rearranged_columns,
             inner: source,
             all_col_stats: vec![],
+            counted_rows: BooleanBufferBuilder::new(0),
             big_df: RecordBatch::new_empty(Schema::empty().into()),
             available_rows: 0..0,
             available_cols: vec![],
             col_stats: vec![],
+            sort: None,
+            filters: vec![],
+            view: None,
+            col_visibility: HashMap::default(),
+            pinned_columns: vec![],
+            n_pinned: 0,
+        }
+    }
+
+    /// The number of rows in the view
+    fn row_count(&self) -> usize {
+        match &self.view {
+            // Rows which were added after sorting/filtering aren't included
+            Some(view) => view.len(),
+            None => self.inner.row_count(),
         }
     }
 
+    /// Reads every value of the column, cast to the type it's displayed as
+    /// (eg. CSV columns come back as strings)
+    fn column_values(
+        &self,
+        column: &str,
+    ) -> anyhow::Result<(
+        DataType,
+        impl Iterator<Item = anyhow::Result<ArrayRef>> + use<>,
+    )> {
+        let schema = self.big_df.schema();
+        let dtype = schema.field_with_name(column)?.data_type().clone();
+        let batches = self.inner.column_batches(&[column])?;
+        let target = dtype.clone();
+        let values = batches.map(move |batch| {
+            let col = batch?.column(0).clone();
+            if col.data_type() == &target {
+                Ok(col)
+            } else {
+                Ok(arrow::compute::cast(&col, &target)?)
+            }
+        });
+        Ok((dtype, values))
+    }
+
+    /// Orders the rows by the given column.  `None` restores the original
+    /// order.
+    fn set_sort(&mut self, sort: Option<(String, bool)>) -> anyhow::Result<()> {
+        self.sort = match sort {
+            None => None,
+            Some((column, descending)) => {
+                let start = Instant::now();
+                let (dtype, keys) = self.column_values(&column)?;
+                let perm = Permutation::build(keys, &dtype, descending)?;
+                debug!(took=?start.elapsed(), "Sorted {} rows by {column}", perm.len());
+                Some(SortOrder {
+                    column,
+                    descending,
+                    perm: Arc::new(perm),
+                })
+            }
+        };
+        self.rebuild_view()
+    }
+
+    /// Hides the rows which don't pass the filter, on top of any existing
+    /// filters
+    fn push_filter(&mut self, txt: &str) -> anyhow::Result<()> {
+        let schema = self.big_df.schema();
+        let filter = Filter::parse(txt, &schema).inspect_err(|e| debug!("\n{}", e.pointer(txt)))?;
+        self.push_parsed_filter(&filter)
+    }
+
+    /// Like `push_filter()`, but for a filter which has already been built
+    fn push_parsed_filter(&mut self, filter: &Filter) -> anyhow::Result<()> {
+        let start = Instant::now();
+        let schema = self.big_df.schema();
+        let mut columns = filter.columns();
+        if columns.is_empty() {
+            // We still need to know how many rows there are
+            columns.extend(schema.fields().first().map(|x| x.name().as_str()));
+        }
+        let mut mask = BooleanBufferBuilder::new(0);
+        for batch in self.inner.column_batches(&columns)? {
+            // Columns are cast to the types they're displayed as
+            mask.append_buffer(filter.evaluate(&batch?)?.values());
+        }
+        let mask = mask.finish();
+        debug!(took=?start.elapsed(), "{} of {} rows pass the filter", mask.count_set_bits(), mask.len());
+        self.filters.push(ActiveFilter { mask });
+        self.rebuild_view()
+    }
+
+    /// Removes the most recently added filter.  Returns false if there
+    /// weren't any.
+    fn pop_filter(&mut self) -> anyhow::Result<bool> {
+        if self.filters.pop().is_none() {
+            return Ok(false);
+        }
+        self.rebuild_view()?;
+        Ok(true)
+    }
+
+    /// Combines the sort order and the filters
+    fn rebuild_view(&mut self) -> anyhow::Result<()> {
+        let perm = self.sort.as_ref().map(|x| x.perm.clone());
+        let mask = self.filters.iter().map(|x| x.mask.clone()).reduce(|a, b| {
+            let len = a.len().min(b.len());
+            &a.slice(0, len) & &b.slice(0, len)
+        });
+        self.view = match (&perm, &mask) {
+            (None, None) => None,
+            _ => Some(Arc::new(RowMap::new(perm, mask.as_ref())?)),
+        };
+        // Invalidate the loaded batch
+        self.available_rows = 0..0;
+        Ok(())
+    }
+
+    /// A short description of the filters for the footer
+    fn filter_status(&self) -> Option<String> {
+        let n_rows = self.filters.iter().map(|x| x.mask.len()).min()?;
+        Some(format!("{} of {n_rows} rows match", self.row_count()))
+    }
+
+    /// Starts computing summary statistics for the column in the background.
+    /// Every row of the source is included, regardless of filters.
+    fn summarise(&self, column: &str, settings: &RenderSettings) -> anyhow::Result<ColumnSummary> {
+        let (dtype, values) = self.column_values(column)?;
+        Ok(ColumnSummary::spawn(
+            column.to_owned(),
+            dtype,
+            values,
+            self.inner.row_count(),
+            settings.clone(),
+        ))
+    }
+
+    /// Starts counting how often each value of the column appears.  Every row
+    /// of the source is included, regardless of filters.
+    fn freq_table(&self, column: &str, settings: &RenderSettings) -> anyhow::Result<FreqJob> {
+        let (dtype, values) = self.column_values(column)?;
+        Ok(FreqJob::spawn(
+            column.to_owned(),
+            dtype,
+            values,
+            self.inner.row_count(),
+            settings.clone(),
+        ))
+    }
+
+    /// Starts searching for the needle in the background.  Matches are
+    /// positions in the current view.
+    fn search(&self, needle: Needle, dir: Dir) -> anyhow::Result<Search> {
+        let job = self.inner.search(&needle)?;
+        Ok(Search::spawn(
+            job,
+            needle,
+            dir,
+            self.inner.row_count(),
+            self.view.clone(),
+        ))
+    }
+
+    /// Loads rows `from..` of the sorted/filtered view
+    fn fetch_view(&mut self, from: usize, view: &RowMap) -> anyhow::Result<RecordBatch> {
+        let rows =
+            view.get_range(from.min(view.len())..(from + VIEW_CHUNK_SIZE).min(view.len()))?;
+        let mut wanted = rows.clone();
+        wanted.sort_unstable();
+        // Load runs of nearby rows together
+        let mut runs: Vec<(usize, RecordBatch)> = vec![];
+        let mut i = 0;
+        while i < wanted.len() {
+            let start = wanted[i];
+            while i + 1 < wanted.len() && wanted[i + 1] < wanted[i] + MAX_RUN_GAP {
+                i += 1;
+            }
+            let len = wanted[i] + 1 - start;
+            let batch = self.inner.fetch_batch(start, len)?;
+            if batch.num_rows() < len {
+                bail!(
+                    "Expected {len} rows starting from {start}; got {}",
+                    batch.num_rows()
+                );
+            }
+            runs.push((start, batch));
+            i += 1;
+        }
+        let Some((_, last)) = runs.last() else {
+            return Ok(RecordBatch::new_empty(self.big_df.schema()));
+        };
+        // The schema may have evolved while we were loading the runs
+        let schema = last.schema();
+        let runs = runs
+            .iter()
+            .map(|(start, batch)| Ok((*start, conform(batch, &schema)?)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let idxs = rows
+            .iter()
+            .map(|row| {
+                let run = runs.partition_point(|(start, _)| start <= row) - 1;
+                (run, row - runs[run].0)
+            })
+            .collect::<Vec<_>>();
+        let cols = (0..schema.fields().len())
+            .map(|col| {
+                let arrays = runs
+                    .iter()
+                    .map(|(_, batch)| batch.column(col).as_ref())
+                    .collect::<Vec<_>>();
+                arrow::compute::interleave(&arrays, &idxs)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(RecordBatch::try_new(schema, cols)?)
+    }
+
     /// If this returns `Ok`, the requested rows should now be available - ie.
     /// you can pass the range into `get_batch()`.
     fn ensure_available(
@@ -191,8 +803,19 @@ impl CachedSource {
 
         debug!("Requested: {rows:?}; available: {:?}", self.available_rows);
         let start = Instant::now();
-        let from = rows.start.saturating_sub(CHUNK_SIZE / 2);
-        match self.inner.fetch_batch(from, CHUNK_SIZE) {
+        let view = self.view.clone();
+        let (from, batch) = match &view {
+            Some(view) => {
+                let from = rows.start.saturating_sub(VIEW_CHUNK_SIZE / 2);
+                (from, self.fetch_view(from, view))
+            }
+            None => {
+                let from = rows.start.saturating_sub(CHUNK_SIZE / 2);
+                (from, self.inner.fetch_batch(from, CHUNK_SIZE))
+            }
+        };
+        let loaded = batch.is_ok();
+        match batch {
             Ok(x) => self.big_df = x,
             Err(e) => warn!("{e}"),
         }
@@ -203,6 +826,24 @@ impl CachedSource {
             self.big_df.get_array_memory_size() / 1024 / 1024,
         );
 
+        // Chunks overlap, and the histograms add up counts, so only rows which
+        // haven't been seen before go into the stats
+        let source_rows = match &view {
+            _ if !loaded => vec![],
+            Some(view) => view.get_range(self.available_rows.clone())?,
+            None => self.available_rows.clone().collect(),
+        };
+        let mut fresh = BooleanBufferBuilder::new(self.big_df.num_rows());
+        for row in source_rows {
+            if row >= self.counted_rows.len() {
+                self.counted_rows
+                    .append_n(row + 1 - self.counted_rows.len(), false);
+            }
+            fresh.append(!self.counted_rows.get_bit(row));
+            self.counted_rows.set_bit(row, true);
+        }
+        let fresh = BooleanArray::new(fresh.finish(), None);
+
         let start = Instant::now();
         for (idx, (field, col)) in self
             .big_df
@@ -212,126 +853,491 @@ impl CachedSource {
             .zip(self.big_df.columns())
             .enumerate()
         {
-            let new_stats = ColumnStats::new(field.name(), col, settings)?;
             match idx.cmp(&self.all_col_stats.len()) {
-                Ordering::Less => self.all_col_stats[idx].merge(new_stats),
-                Ordering::Equal => self.all_col_stats.push(new_stats),
+                Ordering::Less if fresh.true_count() > 0 => {
+                    let col = arrow::compute::filter(col, &fresh)?;
+                    let new_stats = ColumnStats::new(field.name(), &col, settings)?;
+                    self.all_col_stats[idx].merge(new_stats);
+                }
+                Ordering::Less => (),
+                Ordering::Equal => {
+                    let new_stats = ColumnStats::new(field.name(), col, settings)?;
+                    self.all_col_stats.push(new_stats);
+                }
                 Ordering::Greater => panic!(),
             }
         }
+        self.select_columns(settings);
+        debug!(took=?start.elapsed(), "Refined the stats");
+        Ok(())
+    }
+
+    /// Works out which columns to show, and in which order
+    fn select_columns(&mut self, settings: &RenderSettings) {
         self.col_stats.clear();
         self.available_cols.clear();
-        // Explicitly rearranged columns go first
-        for target in &self.rearranged_columns {
-            if let Some((idx, _)) = self.big_df.schema().column_with_name(target) {
+        self.n_pinned = 0;
+        let schema = self.big_df.schema();
+        let visible = schema
+            .fields()
+            .iter()
+            .zip(self.big_df.columns())
+            .map(|(field, col)| {
+                let empty = col.null_count() == col.len();
+                let default = !(settings.hide_empty && empty);
+                let explicit = self.col_visibility.get(field.name()).copied();
+                explicit.unwrap_or(default)
+            })
+            .collect::<Vec<_>>();
+        // Pinned columns go first, then explicitly rearranged ones
+        for target in self.pinned_columns.iter().chain(&self.rearranged_columns) {
+            if let Some((idx, _)) = schema.column_with_name(target)
+                && visible[idx]
+                && !self.available_cols.contains(&idx)
+            {
                 self.available_cols.push(idx);
-                self.col_stats.push(self.all_col_stats[idx].clone());
+            }
+            if self.pinned_columns.contains(target) {
+                self.n_pinned = self.available_cols.len();
             }
         }
         let explicit_up_to = self.available_cols.len();
-        for (idx, col) in self.big_df.columns().iter().enumerate() {
+        for (idx, visible) in visible.into_iter().enumerate() {
             let explicit = self.available_cols[..explicit_up_to].contains(&idx);
-            let hidden = settings.hide_empty && col.null_count() == col.len();
-            if !explicit && !hidden {
+            if !explicit && visible {
                 self.available_cols.push(idx);
-                self.col_stats.push(self.all_col_stats[idx].clone());
             }
         }
-        debug!(took=?start.elapsed(), "Refined the stats");
-        Ok(())
+        for idx in &self.available_cols {
+            self.col_stats.push(self.all_col_stats[*idx].clone());
+        }
+    }
+
+    /// Every column, with its type and whether it's visible
+    fn all_columns(&self) -> Vec<(String, DataType, bool)> {
+        self.big_df
+            .schema()
+            .fields()
+            .iter()
+            .enumerate()
+            .map(|(idx, field)| {
+                let visible = self.available_cols.contains(&idx);
+                (field.name().clone(), field.data_type().clone(), visible)
+            })
+            .collect()
     }
 
-    fn get_batch(&self, rows: Range<usize>, cols: Range<usize>) -> anyhow::Result<RecordBatch> {
+    fn set_column_visible(&mut self, name: &str, visible: bool, settings: &RenderSettings) {
+        self.col_visibility.insert(name.to_owned(), visible);
+        self.select_columns(settings);
+    }
+
+    fn unhide_all_columns(&mut self, settings: &RenderSettings) {
+        for field in self.big_df.schema().fields() {
+            self.col_visibility.insert(field.name().clone(), true);
+        }
+        self.select_columns(settings);
+    }
+
+    /// Swaps the nth visible column with its neighbour.  Pinned columns only
+    /// swap with other pinned columns, and likewise for unpinned ones.
+    /// Returns the column's new position.
+    fn move_column(&mut self, col: usize, right: bool, settings: &RenderSettings) -> usize {
+        let other = if right { col + 1 } else { col.wrapping_sub(1) };
+        let pinned = |x: usize| x < self.n_pinned;
+        if other >= self.available_cols.len() || pinned(col) != pinned(other) {
+            return col;
+        }
+        let mut order = (0..self.available_cols.len())
+            .filter_map(|x| self.column_name(x))
+            .collect::<Vec<_>>();
+        order.swap(col, other);
+        let rest = order.split_off(self.n_pinned);
+        // Hidden columns stay pinned
+        let hidden_pinned = self
+            .pinned_columns
+            .iter()
+            .filter(|x| !order.contains(x) && !rest.contains(x))
+            .cloned()
+            .collect::<Vec<_>>();
+        order.extend(hidden_pinned);
+        self.pinned_columns = order;
+        self.rearranged_columns = rest;
+        self.select_columns(settings);
+        other
+    }
+
+    /// Pins the nth visible column, or unpins it if it's already pinned.
+    /// Returns the column's new position.
+    fn toggle_pin(&mut self, col: usize, settings: &RenderSettings) -> usize {
+        let Some(name) = self.column_name(col) else {
+            return col;
+        };
+        match self.pinned_columns.iter().position(|x| *x == name) {
+            Some(idx) => {
+                self.pinned_columns.remove(idx);
+            }
+            None => self.pinned_columns.push(name.clone()),
+        }
+        self.select_columns(settings);
+        (0..self.available_cols.len())
+            .find(|x| self.column_name(*x).as_ref() == Some(&name))
+            .unwrap_or(col)
+    }
+
+    /// Every column of the given row of the view, including hidden ones.  If
+    /// the row doesn't exist, the batch is empty.
+    fn record(&mut self, row: usize, settings: &RenderSettings) -> anyhow::Result<RecordBatch> {
+        self.ensure_available(row..row + 1, settings)?;
+        if !self.available_rows.contains(&row) {
+            return Ok(self.big_df.slice(0, 0));
+        }
+        Ok(self.big_df.slice(row - self.available_rows.start, 1))
+    }
+
+    fn has_column(&self, name: &str) -> bool {
+        self.big_df.schema().column_with_name(name).is_some()
+    }
+
+    /// The name of the nth visible column
+    fn column_name(&self, col: usize) -> Option<String> {
+        let idx = *self.available_cols.get(col)?;
+        Some(self.big_df.schema().field(idx).name().clone())
+    }
+
+    /// `cols` are positions among the visible columns
+    fn get_batch(&self, rows: Range<usize>, cols: &[usize]) -> anyhow::Result<RecordBatch> {
         debug!(?rows, ?cols, "Slicing big df");
-        let enabled_cols = &self.available_cols[cols];
+        let enabled_cols = cols
+            .iter()
+            .map(|x| self.available_cols[*x])
+            .collect::<Vec<_>>();
         let offset = rows.start - self.available_rows.start;
         let len = rows.end.min(self.available_rows.end) - rows.start;
-        let mini_df = self.big_df.project(enabled_cols)?.slice(offset, len);
+        let mini_df = self.big_df.project(&enabled_cols)?.slice(offset, len);
         Ok(mini_df)
     }
 }
 
 fn runloop(
     stdout: &mut impl Write,
-    mut source: CachedSource,
-    settings: RenderSettings,
+    mut tabs: Vec<Tab>,
+    mut settings: RenderSettings,
+    mut search_opts: SearchOpts,
+    run_query: impl Fn(&[PathBuf], &str) -> anyhow::Result<Box<dyn DataSource>>,
 ) -> anyhow::Result<()> {
     let mut term_size = terminal::size()?;
-    let mut start_col: usize = 0;
-    let mut start_row: usize = 0;
+    let mut cur_tab = 0;
     let mut prompt = Prompt::default();
     let mut file_refresh_interval = Duration::from_millis(10);
     let mut last_file_refresh = Instant::now();
-    let mut total_rows = source.inner.row_count();
     let mut dirty = true;
     let mut col_widths = vec![];
-    let mut highlights = HashSet::<usize>::default();
-    let mut search_matches = vec![];
-    let mut search_dir = Dir::Forward;
+    // Shown in the footer until the next keypress
+    let mut message: Option<String> = None;
+    // The selected row of the column picker, if it's open
+    let mut picker: Option<usize> = None;
+    let mut detail: Option<Detail> = None;
+    let mut summary: Option<ColumnSummary> = None;
 
-    // Load the initial batch
-    source.ensure_available(0..0, &settings)?;
+    // Load the initial batches
+    for tab in &mut tabs {
+        tab.source.ensure_available(0..0, &settings)?;
+        tab.total_rows = tab.source.row_count();
+    }
 
     loop {
+        let n_tabs = tabs.len();
+
         if last_file_refresh.elapsed() > file_refresh_interval {
-            let new_rows = source.inner.check_for_new_rows()?;
-            if new_rows == 0 {
-                file_refresh_interval = (file_refresh_interval * 10).min(Duration::from_secs(1));
-            } else {
-                total_rows = source.inner.row_count();
+            // Background tabs keep up too, so that they're current when the
+            // user switches to them
+            let mut any_new_rows = false;
+            for (idx, tab) in tabs.iter_mut().enumerate() {
+                if tab.source.inner.check_for_new_rows()? > 0 {
+                    tab.total_rows = tab.source.row_count();
+                    any_new_rows = true;
+                    dirty |= idx == cur_tab;
+                }
+            }
+            if any_new_rows {
                 file_refresh_interval = Duration::from_millis(10);
-                dirty = true;
+            } else {
+                file_refresh_interval = (file_refresh_interval * 10).min(Duration::from_secs(1));
             }
             last_file_refresh = Instant::now();
+            if let Some(e) = SPOOL_ERROR.lock().unwrap().take() {
+                message = Some(format!("(couldn't read the input: {e})"));
+                dirty = true;
+            }
+        }
+
+        let tab = &mut tabs[cur_tab];
+
+        if let Some(search) = &mut tab.search {
+            if search.poll() {
+                dirty = true;
+            }
+            if tab.jump_pending {
+                // In the reverse direction, we can't be sure which match is the
+                // nearest one until everything before the current row is searched
+                let ready = match search.dir {
+                    Dir::Forward => true,
+                    Dir::Reverse => search.has_scanned(tab.cursor_row),
+                };
+                if ready && let Some(x) = search.next_match(tab.cursor_row, search.dir) {
+                    tab.cursor_row = x;
+                    tab.jump_pending = false;
+                    dirty = true;
+                }
+                if !search.is_running() {
+                    tab.jump_pending = false;
+                }
+            }
+        }
+
+        if let Some(job) = &mut tab.freq_job
+            && job.poll()
+        {
+            dirty = true;
+            if let Some(result) = job.take_result() {
+                let column = job.column.clone();
+                tab.freq_job = None;
+                let result = result.and_then(|table| {
+                    let mut source = CachedSource::new(Box::new(table), vec![]);
+                    source.ensure_available(0..0, &settings)?;
+                    Ok(source)
+                });
+                match result {
+                    Ok(source) => {
+                        let mut sheet = Tab::new(vec![], source);
+                        sheet.name = format!("{column} frequencies");
+                        sheet.derived = true;
+                        sheet.total_rows = sheet.source.row_count();
+                        let parent = std::mem::replace(tab, sheet);
+                        tab.parent = Some((Box::new(parent), column));
+                    }
+                    Err(e) => {
+                        warn!("Couldn't count the values of {column}: {e}");
+                        message = Some("(couldn't count the values)".to_owned());
+                    }
+                }
+            }
+        }
+
+        if let Some(summary) = &mut summary
+            && summary.poll()
+        {
+            dirty = true;
+        }
+
+        if dirty && let Some(summary) = &summary {
+            draw_summary(
+                stdout,
+                summary,
+                term_size.0,
+                term_size.1,
+                &settings,
+                &prompt,
+            )?;
+            dirty = false;
+        }
+
+        if dirty && let Some(detail) = &mut detail {
+            detail.scroll = draw_detail(
+                stdout,
+                &detail.record,
+                detail.row,
+                detail.scroll,
+                term_size.0,
+                term_size.1,
+                &settings,
+                &prompt,
+            )?;
+            dirty = false;
+        }
+
+        if dirty && let Some(cursor) = picker {
+            draw_column_picker(
+                stdout,
+                &tab.source.all_columns(),
+                cursor,
+                term_size.0,
+                term_size.1,
+                &prompt,
+            )?;
+            dirty = false;
+        }
+
+        if dirty && tab.transposed {
+            let idx_width = if tab.total_rows == 0 {
+                0
+            } else {
+                tab.total_rows.ilog10() as u16
+            } + 1;
+
+            // Scroll the fields vertically if the cursor has left the screen.
+            // There are no histograms in this view, so the header is always
+            // `HEADER_HEIGHT` rows, whatever `settings.header_height()` says.
+            let height = (term_size.1 - HEADER_HEIGHT - FOOTER_HEIGHT) as usize;
+            let n_cols = tab.source.col_stats.len();
+            tab.cursor_col = tab.cursor_col.min(n_cols.saturating_sub(1));
+            if tab.cursor_col < tab.start_col {
+                tab.start_col = tab.cursor_col;
+            } else if tab.cursor_col >= tab.start_col + height {
+                tab.start_col = tab.cursor_col + 1 - height;
+            }
+            let cols = (tab.start_col..(tab.start_col + height).min(n_cols)).collect::<Vec<_>>();
+
+            // Every record gets the same width, and we show as many as fit
+            let max_width = term_size.0 / 3;
+            let name_width = cols
+                .iter()
+                .filter_map(|x| tab.source.column_name(*x))
+                .map(|x| x.chars().count() as u16 + 2)
+                .max()
+                .unwrap_or(0)
+                .min(max_width);
+            let record_width = cols
+                .iter()
+                .map(|x| tab.source.col_stats[*x].ideal_width)
+                .max()
+                .unwrap_or(0)
+                .min(max_width)
+                .max(idx_width);
+            let n_records = ((term_size.0 - name_width) / (record_width + 3)).max(1) as usize;
+
+            // ...and the records horizontally
+            if prompt.is_following() {
+                tab.cursor_row = tab.total_rows.saturating_sub(1);
+            }
+            tab.cursor_row = tab.cursor_row.min(tab.total_rows.saturating_sub(1));
+            if tab.cursor_row < tab.start_row {
+                tab.start_row = tab.cursor_row;
+            } else if tab.cursor_row >= tab.start_row + n_records {
+                tab.start_row = tab.cursor_row + 1 - n_records;
+            }
+            let end_row = (tab.start_row + n_records).min(tab.total_rows);
+            let rows = tab.start_row..end_row;
+            tab.source.ensure_available(rows.clone(), &settings)?;
+
+            let col_stats = cols
+                .iter()
+                .map(|x| &tab.source.col_stats[*x])
+                .collect::<Vec<_>>();
+            let batch = tab.source.get_batch(rows, &cols)?;
+            draw_transposed(
+                stdout,
+                tab.start_row,
+                batch,
+                term_size.0,
+                term_size.1,
+                name_width,
+                record_width,
+                tab.total_rows,
+                &col_stats,
+                &settings,
+                &prompt,
+                &tab.highlights,
+                tab.source
+                    .sort
+                    .as_ref()
+                    .map(|x| (x.column.as_str(), x.descending)),
+                tab.search.as_ref(),
+                &message
+                    .clone()
+                    .unwrap_or_else(|| tab.status(cur_tab, n_tabs)),
+                Some((
+                    tab.cursor_row - tab.start_row,
+                    tab.cursor_col - tab.start_col,
+                )),
+            )?;
+            dirty = false;
         }
 
         if dirty {
-            let idx_width = if total_rows == 0 {
+            let idx_width = if tab.total_rows == 0 {
                 0
             } else {
-                total_rows.ilog10() as u16
+                tab.total_rows.ilog10() as u16
             } + 1;
 
+            // Scroll the viewport vertically if the cursor has left it
+            let height = (term_size.1 - settings.header_height() - FOOTER_HEIGHT) as usize;
             if prompt.is_following() {
-                start_row = total_rows.saturating_sub(term_size.1 as usize - 2);
+                tab.cursor_row = tab.total_rows.saturating_sub(1);
             }
-            let end_row = (start_row + (term_size.1 - HEADER_HEIGHT - FOOTER_HEIGHT) as usize)
-                .min(total_rows);
-            let rows = start_row..end_row;
-            source.ensure_available(rows.clone(), &settings)?;
-
-            col_widths.clear();
-            let mut remaining = term_size.0 - idx_width - 2;
-            for stats in &source.col_stats[start_col..] {
-                if remaining >= 1 {
-                    let w = stats.ideal_width.min(remaining);
-                    remaining = remaining.saturating_sub(3 + w);
-                    col_widths.push(w);
-                }
+            tab.cursor_row = tab.cursor_row.min(tab.total_rows.saturating_sub(1));
+            if tab.cursor_row < tab.start_row {
+                tab.start_row = tab.cursor_row;
+            } else if tab.cursor_row >= tab.start_row + height {
+                tab.start_row = tab.cursor_row + 1 - height;
             }
-            let end_col = start_col + col_widths.len();
-            let cols = start_col..end_col;
+            let end_row = (tab.start_row + height).min(tab.total_rows);
+            let rows = tab.start_row..end_row;
+            tab.source.ensure_available(rows.clone(), &settings)?;
+
+            // ...and horizontally
+            let n_cols = tab.source.col_stats.len();
+            let n_pinned = tab.source.n_pinned;
+            tab.cursor_col = tab.cursor_col.min(n_cols.saturating_sub(1));
+            let width = term_size.0 - idx_width - 2;
+            let cols = loop {
+                let (cols, widths) =
+                    fit_columns(&tab.source.col_stats, n_pinned, tab.start_col, width);
+                let offscreen = tab.cursor_col >= n_pinned && !cols.contains(&tab.cursor_col);
+                if !offscreen || tab.start_col >= n_cols {
+                    col_widths = widths;
+                    break cols;
+                }
+                if tab.cursor_col < tab.start_col {
+                    tab.start_col = tab.cursor_col;
+                } else {
+                    tab.start_col = tab.start_col.max(n_pinned) + 1;
+                }
+            };
+            let col_stats = cols
+                .iter()
+                .map(|x| &tab.source.col_stats[*x])
+                .collect::<Vec<_>>();
 
-            let batch = source.get_batch(rows, cols.clone())?;
+            let batch = tab.source.get_batch(rows, &cols)?;
             draw(
                 stdout,
-                start_row,
+                tab.start_row,
                 batch,
                 term_size.0,
                 term_size.1,
                 idx_width,
                 &col_widths,
-                total_rows,
-                &source.col_stats[cols],
+                tab.total_rows,
+                &col_stats,
+                n_pinned.min(cols.len()),
                 &settings,
                 &prompt,
-                &highlights,
-                search_matches.len(),
+                &tab.highlights,
+                tab.source
+                    .sort
+                    .as_ref()
+                    .map(|x| (x.column.as_str(), x.descending)),
+                tab.search.as_ref(),
+                &message
+                    .clone()
+                    .unwrap_or_else(|| tab.status(cur_tab, n_tabs)),
+                cols.iter()
+                    .position(|x| *x == tab.cursor_col)
+                    .map(|col| (tab.cursor_row - tab.start_row, col)),
             )?;
             dirty = false;
         }
 
-        if event::poll(file_refresh_interval)? {
+        let mut poll_interval = file_refresh_interval;
+        if tab.search.as_ref().is_some_and(Search::is_running)
+            || summary.as_ref().is_some_and(ColumnSummary::is_running)
+        {
+            poll_interval = poll_interval.min(Duration::from_millis(50));
+        }
+        if event::poll(poll_interval)? {
             let event = event::read()?;
             let cmd = match event {
                 event::Event::Key(k) => match k.code {
@@ -340,6 +1346,22 @@ fn runloop(
                     {
                         return Ok(());
                     }
+                    event::KeyCode::Esc
+                        if prompt.is_idle()
+                            && tab.search.as_ref().is_some_and(Search::is_running) =>
+                    {
+                        tab.search.as_mut().unwrap().cancel();
+                        tab.jump_pending = false;
+                        Some(Cmd::Redraw)
+                    }
+                    event::KeyCode::Enter if prompt.is_idle() && tab.parent.is_some() => {
+                        Some(Cmd::PickValue)
+                    }
+                    event::KeyCode::Esc | event::KeyCode::Char('q')
+                        if prompt.is_idle() && tab.parent.is_some() =>
+                    {
+                        Some(Cmd::CloseSheet)
+                    }
                     code => prompt.handle_key(code),
                 },
                 event::Event::Mouse(ev) => prompt.handle_mouse(ev),
@@ -350,61 +1372,358 @@ fn runloop(
                 _ => None,
             };
             if let Some(cmd) = cmd {
+                message = None;
                 match cmd {
                     Cmd::Redraw => (),
                     Cmd::ColRight => {
-                        start_col = (start_col + 1).min(source.col_stats.len().saturating_sub(1))
+                        tab.cursor_col =
+                            (tab.cursor_col + 1).min(tab.source.col_stats.len().saturating_sub(1))
+                    }
+                    Cmd::ColLeft => tab.cursor_col = tab.cursor_col.saturating_sub(1),
+                    Cmd::RowDown => {
+                        tab.cursor_row = (tab.cursor_row + 1).min(tab.total_rows.saturating_sub(1))
+                    }
+                    Cmd::RowUp => tab.cursor_row = tab.cursor_row.saturating_sub(1),
+                    Cmd::RowBottom => tab.cursor_row = tab.total_rows.saturating_sub(1),
+                    Cmd::RowTop => tab.cursor_row = 0,
+                    Cmd::RowPgUp => {
+                        let page = term_size.1 as usize - 2;
+                        tab.cursor_row = tab.cursor_row.saturating_sub(page);
+                        tab.start_row = tab.start_row.saturating_sub(page);
                     }
-                    Cmd::ColLeft => start_col = start_col.saturating_sub(1),
-                    Cmd::RowDown => start_row = (start_row + 1).min(total_rows.saturating_sub(1)),
-                    Cmd::RowUp => start_row = start_row.saturating_sub(1),
-                    Cmd::RowBottom => start_row = total_rows.saturating_sub(1),
-                    Cmd::RowTop => start_row = 0,
-                    Cmd::RowPgUp => start_row = start_row.saturating_sub(term_size.1 as usize - 2),
                     Cmd::RowPgDown => {
-                        start_row =
-                            (start_row + term_size.1 as usize - 2).min(total_rows.saturating_sub(1))
+                        let page = term_size.1 as usize - 2;
+                        let last_row = tab.total_rows.saturating_sub(1);
+                        tab.cursor_row = (tab.cursor_row + page).min(last_row);
+                        tab.start_row = (tab.start_row + page).min(last_row);
                     }
-                    Cmd::RowGoTo(x) => start_row = x.min(total_rows.saturating_sub(1)),
-                    Cmd::Search(needle, dir) => {
-                        search_matches = source.inner.search(&needle)?;
-                        search_dir = dir;
-                        if let Some(x) = next_match(&search_matches, start_row, search_dir) {
-                            start_row = x;
+                    Cmd::RowGoTo(x) => tab.cursor_row = x.min(tab.total_rows.saturating_sub(1)),
+                    Cmd::Search(pattern, dir) => {
+                        // "col:pattern" only searches the named column
+                        let (column, pattern) = match pattern.split_once(':') {
+                            Some((col, rest)) if tab.source.has_column(col) => {
+                                (Some(col.to_owned()), rest)
+                            }
+                            _ => (None, pattern.as_str()),
+                        };
+                        match Needle::new(pattern, search_opts) {
+                            Ok(needle) => match tab.source.search(needle.in_column(column), dir) {
+                                Ok(search) => {
+                                    tab.search = Some(search);
+                                    tab.jump_pending = true;
+                                }
+                                Err(e) => {
+                                    warn!("Couldn't search: {e}");
+                                    message = Some("(couldn't search)".to_owned());
+                                }
+                            },
+                            Err(e) => {
+                                warn!("{e}");
+                                message = Some("(invalid pattern)".to_owned());
+                            }
                         }
                     }
                     Cmd::SearchNext => {
-                        if let Some(x) = next_match(&search_matches, start_row, search_dir) {
-                            start_row = x;
+                        if let Some(search) = &tab.search
+                            && let Some(x) = search.next_match(tab.cursor_row, search.dir)
+                        {
+                            tab.cursor_row = x;
                         }
+                        tab.jump_pending = false;
                     }
                     Cmd::SearchPrev => {
-                        if let Some(x) = next_match(&search_matches, start_row, search_dir.invert())
+                        if let Some(search) = &tab.search
+                            && let Some(x) = search.next_match(tab.cursor_row, search.dir.invert())
                         {
-                            start_row = x;
+                            tab.cursor_row = x;
+                        }
+                        tab.jump_pending = false;
+                    }
+                    Cmd::CycleSort => {
+                        if let Some(column) = tab.source.column_name(tab.cursor_col) {
+                            // Ascending -> descending -> unsorted
+                            let sort = match &tab.source.sort {
+                                Some(x) if x.column == column && !x.descending => {
+                                    Some((column, true))
+                                }
+                                Some(x) if x.column == column => None,
+                                _ => Some((column, false)),
+                            };
+                            match tab.source.set_sort(sort) {
+                                Ok(()) => tab.total_rows = tab.source.row_count(),
+                                Err(e) => {
+                                    warn!("Couldn't sort: {e}");
+                                    message = Some("(couldn't sort)".to_owned());
+                                }
+                            }
+                            rerun_search(&tab.source, &mut tab.search, &mut message);
+                        }
+                    }
+                    Cmd::Filter(txt) => {
+                        match tab.source.push_filter(&txt) {
+                            Ok(()) => {
+                                tab.total_rows = tab.source.row_count();
+                                tab.cursor_row = 0;
+                            }
+                            Err(e) => {
+                                warn!("Couldn't filter: {e}");
+                                message = Some(format!("(invalid filter: {e})"));
+                            }
+                        }
+                        rerun_search(&tab.source, &mut tab.search, &mut message);
+                    }
+                    Cmd::PopFilter => {
+                        match tab.source.pop_filter() {
+                            Ok(true) => tab.total_rows = tab.source.row_count(),
+                            Ok(false) => message = Some("(no filters)".to_owned()),
+                            Err(e) => {
+                                warn!("Couldn't remove the filter: {e}");
+                                message = Some("(couldn't remove the filter)".to_owned());
+                            }
                         }
+                        rerun_search(&tab.source, &mut tab.search, &mut message);
                     }
-                    Cmd::ToggleHighlight(row) => {
-                        let row = start_row + row as usize - 1;
-                        if highlights.contains(&row) {
-                            highlights.remove(&row);
+                    Cmd::Command(txt) => match txt.trim().split_once(' ') {
+                        _ if txt.trim().is_empty() => (),
+                        Some(("sql", _)) if tab.derived => {
+                            message = Some("(can't query this sheet)".to_owned());
+                        }
+                        Some(("sql", sql)) => {
+                            // The current tab is kept if the query fails
+                            let source = run_query(&tab.paths, sql).and_then(|inner| {
+                                let mut source =
+                                    CachedSource::new(inner, tab.source.rearranged_columns.clone());
+                                source.ensure_available(0..0, &settings)?;
+                                Ok(source)
+                            });
+                            match source {
+                                Ok(source) => {
+                                    tab.source = source;
+                                    tab.total_rows = tab.source.row_count();
+                                    tab.start_row = 0;
+                                    tab.start_col = 0;
+                                    tab.cursor_row = 0;
+                                    tab.cursor_col = 0;
+                                    tab.highlights.clear();
+                                    tab.search = None;
+                                    tab.jump_pending = false;
+                                }
+                                Err(e) => {
+                                    warn!("Query failed: {e}");
+                                    message = Some(format!("({e})"));
+                                }
+                            }
+                        }
+                        _ => message = Some(format!("(unknown command: {txt})")),
+                    },
+                    Cmd::ToggleRegex => {
+                        search_opts.regex = !search_opts.regex;
+                        message = Some(if search_opts.regex {
+                            "Search patterns are regexes".to_owned()
                         } else {
-                            highlights.insert(row);
+                            "Search patterns are plain strings".to_owned()
+                        });
+                    }
+                    Cmd::ToggleCase(mode) => {
+                        search_opts.case = if search_opts.case == mode {
+                            CaseMode::Sensitive
+                        } else {
+                            mode
+                        };
+                        message = Some(match search_opts.case {
+                            CaseMode::Sensitive => "Case is significant in searches".to_owned(),
+                            CaseMode::Smart => "Ignore case in searches".to_owned(),
+                            CaseMode::Insensitive => {
+                                "Ignore case in searches and in patterns".to_owned()
+                            }
+                        });
+                    }
+                    // Rows aren't rows when transposed
+                    Cmd::Click(_) if tab.transposed => (),
+                    Cmd::Click(y) => {
+                        let header_height = settings.header_height();
+                        let row = tab.start_row + (y.saturating_sub(header_height)) as usize;
+                        if y >= header_height && row < tab.total_rows {
+                            tab.cursor_row = row;
+                            toggle_highlight(&mut tab.highlights, row);
+                        }
+                    }
+                    Cmd::ToggleHighlight => toggle_highlight(&mut tab.highlights, tab.cursor_row),
+                    Cmd::HideColumn => {
+                        if let Some(column) = tab.source.column_name(tab.cursor_col) {
+                            tab.source.set_column_visible(&column, false, &settings);
+                        }
+                    }
+                    Cmd::UnhideColumns => tab.source.unhide_all_columns(&settings),
+                    Cmd::OpenColumnPicker => picker = Some(0),
+                    Cmd::ClosePicker => picker = None,
+                    Cmd::PickerUp => picker = picker.map(|x| x.saturating_sub(1)),
+                    Cmd::PickerDown => {
+                        let n_cols = tab.source.all_columns().len();
+                        picker = picker.map(|x| (x + 1).min(n_cols.saturating_sub(1)));
+                    }
+                    Cmd::PickerToggle => {
+                        let columns = tab.source.all_columns();
+                        if let Some((name, _, visible)) = picker.and_then(|x| columns.get(x)) {
+                            tab.source.set_column_visible(name, !visible, &settings);
+                        }
+                    }
+                    Cmd::OpenDetail => match tab.source.record(tab.cursor_row, &settings) {
+                        Ok(record) => {
+                            detail = Some(Detail {
+                                record,
+                                row: tab.cursor_row,
+                                scroll: 0,
+                            })
+                        }
+                        Err(e) => {
+                            warn!("Couldn't load row {}: {e}", tab.cursor_row);
+                            message = Some("(couldn't load the row)".to_owned());
+                            prompt.reset();
+                        }
+                    },
+                    Cmd::CloseDetail => detail = None,
+                    Cmd::OpenSummary => match tab.source.column_name(tab.cursor_col) {
+                        Some(column) => match tab.source.summarise(&column, &settings) {
+                            Ok(x) => summary = Some(x),
+                            Err(e) => {
+                                warn!("Couldn't summarise {column}: {e}");
+                                message = Some("(couldn't summarise the column)".to_owned());
+                                prompt.reset();
+                            }
+                        },
+                        None => prompt.reset(),
+                    },
+                    Cmd::CloseSummary => summary = None,
+                    Cmd::ToggleHistograms => settings.histograms = !settings.histograms,
+                    Cmd::OpenFreqTable => {
+                        if let Some(column) = tab.source.column_name(tab.cursor_col) {
+                            match tab.source.freq_table(&column, &settings) {
+                                Ok(job) => tab.freq_job = Some(job),
+                                Err(e) => {
+                                    warn!("Couldn't count the values of {column}: {e}");
+                                    message = Some("(couldn't count the values)".to_owned());
+                                }
+                            }
+                        }
+                    }
+                    Cmd::PickValue => match tab.source.record(tab.cursor_row, &settings) {
+                        Ok(record) => {
+                            if let Some((parent, column)) = tab.parent.take() {
+                                *tab = *parent;
+                                if let Some(value) = record.column_by_name("value")
+                                    && !value.is_empty()
+                                {
+                                    let filter = Filter::equals(&column, value.as_ref())
+                                        .and_then(|x| tab.source.push_parsed_filter(&x));
+                                    match filter {
+                                        Ok(()) => {
+                                            tab.total_rows = tab.source.row_count();
+                                            tab.cursor_row = 0;
+                                        }
+                                        Err(e) => {
+                                            warn!("Couldn't filter: {e}");
+                                            message = Some(format!("(invalid filter: {e})"));
+                                        }
+                                    }
+                                    rerun_search(&tab.source, &mut tab.search, &mut message);
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            warn!("Couldn't load row {}: {e}", tab.cursor_row);
+                            message = Some("(couldn't load the row)".to_owned());
+                        }
+                    },
+                    Cmd::CloseSheet => {
+                        if let Some((parent, _)) = tab.parent.take() {
+                            *tab = *parent;
                         }
                     }
+                    Cmd::Transpose => {
+                        tab.transposed = !tab.transposed;
+                        tab.start_row = tab.cursor_row;
+                        tab.start_col = tab.cursor_col;
+                    }
+                    Cmd::DetailUp | Cmd::DetailDown | Cmd::DetailPgUp | Cmd::DetailPgDown => {
+                        let page = term_size.1 as usize - 2;
+                        if let Some(detail) = &mut detail {
+                            detail.scroll = match cmd {
+                                Cmd::DetailUp => detail.scroll.saturating_sub(1),
+                                Cmd::DetailDown => detail.scroll + 1,
+                                Cmd::DetailPgUp => detail.scroll.saturating_sub(page),
+                                _ => detail.scroll + page,
+                            };
+                        }
+                    }
+                    Cmd::MoveColumn(right) => {
+                        tab.cursor_col = tab.source.move_column(tab.cursor_col, right, &settings);
+                    }
+                    Cmd::TogglePin => {
+                        tab.cursor_col = tab.source.toggle_pin(tab.cursor_col, &settings);
+                    }
+                    Cmd::NextTab => cur_tab = (cur_tab + 1) % n_tabs,
+                    Cmd::PrevTab => cur_tab = (cur_tab + n_tabs - 1) % n_tabs,
                     Cmd::Exit => return Ok(()),
                 }
+                // The summary follows the cursor
+                if let Some(x) = &summary
+                    && let Some(column) = tab.source.column_name(tab.cursor_col)
+                    && column != x.column
+                {
+                    summary = tab
+                        .source
+                        .summarise(&column, &settings)
+                        .inspect_err(|e| warn!("Couldn't summarise {column}: {e}"))
+                        .ok();
+                }
                 dirty = true;
             }
         }
     }
 }
 
-fn next_match(matches: &[usize], current_row: usize, dir: Dir) -> Option<usize> {
-    // TODO: Binary search
-    match dir {
-        Dir::Forward => matches.iter().copied().find(|x| *x > current_row),
-        Dir::Reverse => matches.iter().copied().rfind(|x| *x < current_row),
+/// Chooses which columns to show: first the pinned ones, then as many of the
+/// others (starting from `start_col`) as will fit.  Returns the columns and
+/// their widths.
+fn fit_columns(
+    col_stats: &[ColumnStats],
+    n_pinned: usize,
+    start_col: usize,
+    width: u16,
+) -> (Vec<usize>, Vec<u16>) {
+    let mut cols = vec![];
+    let mut widths = vec![];
+    let mut remaining = width;
+    let scrolled = start_col.max(n_pinned)..col_stats.len();
+    for col in (0..n_pinned).chain(scrolled) {
+        if remaining >= 1 {
+            let w = col_stats[col].ideal_width.min(remaining);
+            remaining = remaining.saturating_sub(3 + w);
+            widths.push(w);
+            cols.push(col);
+        }
+    }
+    (cols, widths)
+}
+
+fn toggle_highlight(highlights: &mut HashSet<usize>, row: usize) {
+    if !highlights.remove(&row) {
+        highlights.insert(row);
+    }
+}
+
+/// Matches are positions in the view, so they need to be recomputed when the
+/// view changes.  If the search can't be restarted, it's dropped.
+fn rerun_search(source: &CachedSource, search: &mut Option<Search>, message: &mut Option<String>) {
+    if let Some(old) = search.take() {
+        match source.search(old.needle.clone(), old.dir) {
+            Ok(x) => *search = Some(x),
+            Err(e) => {
+                warn!("Couldn't search: {e}");
+                *message = Some("(couldn't search)".to_owned());
+            }
+        }
     }
 }
 
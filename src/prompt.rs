@@ -1,3 +1,4 @@
+use crate::search::CaseMode;
 use crossterm::event::{KeyCode, MouseButton, MouseEvent, MouseEventKind};
 use std::io::Write;
 
@@ -12,7 +13,19 @@ enum Mode {
     #[default]
     Normal,
     Search(Dir),
+    /// Entering a filter expression (like less's `&`)
+    Filter,
+    /// Entering a command, eg. `sql ...`
+    Command,
     Follow,
+    /// Waiting for the name of an option to toggle (like less's `-`)
+    Option,
+    /// Choosing which columns to show
+    ColumnPicker,
+    /// Looking at a single record
+    Detail,
+    /// Looking at a column's summary statistics
+    Summary,
 }
 
 #[derive(Copy, Clone)]
@@ -44,7 +57,42 @@ pub enum Cmd {
     Search(String, Dir),
     SearchNext,
     SearchPrev,
-    ToggleHighlight(u16),
+    Filter(String),
+    PopFilter,
+    Command(String),
+    CycleSort,
+    ToggleRegex,
+    ToggleCase(CaseMode),
+    /// The mouse was clicked on this line of the screen
+    Click(u16),
+    ToggleHighlight,
+    NextTab,
+    PrevTab,
+    /// Move the current column right (true) or left (false)
+    MoveColumn(bool),
+    TogglePin,
+    HideColumn,
+    UnhideColumns,
+    OpenColumnPicker,
+    ClosePicker,
+    PickerUp,
+    PickerDown,
+    PickerToggle,
+    OpenDetail,
+    Transpose,
+    OpenSummary,
+    OpenFreqTable,
+    ToggleHistograms,
+    /// Go back to the sheet the frequency table was opened from, filtered to
+    /// the selected value
+    PickValue,
+    CloseSheet,
+    CloseSummary,
+    CloseDetail,
+    DetailUp,
+    DetailDown,
+    DetailPgUp,
+    DetailPgDown,
 }
 
 impl Prompt {
@@ -53,7 +101,11 @@ impl Prompt {
             Mode::Normal => ":",
             Mode::Search(Dir::Forward) => "/",
             Mode::Search(Dir::Reverse) => "?",
+            Mode::Filter => "&",
+            Mode::Command => ":",
             Mode::Follow => ">",
+            Mode::Option => "-",
+            Mode::ColumnPicker | Mode::Detail | Mode::Summary => ":",
         };
         write!(stdout, "{}{}", ps1, self.input)?;
         Ok(())
@@ -63,6 +115,17 @@ impl Prompt {
         matches!(self.mode, Mode::Follow)
     }
 
+    /// Whether the prompt is in normal mode, with no pending input
+    pub fn is_idle(&self) -> bool {
+        matches!(self.mode, Mode::Normal) && self.input.is_empty()
+    }
+
+    /// Goes back to normal mode, eg. if the view a key was meant to open
+    /// couldn't be opened after all
+    pub fn reset(&mut self) {
+        self.mode = Mode::Normal;
+    }
+
     pub fn handle_key(&mut self, key: KeyCode) -> Option<Cmd> {
         match self.mode {
             Mode::Normal => match key {
@@ -71,6 +134,8 @@ impl Prompt {
                 KeyCode::Down | KeyCode::Char('j') => Some(Cmd::RowDown),
                 KeyCode::Up | KeyCode::Char('k') => Some(Cmd::RowUp),
                 KeyCode::End | KeyCode::Char('G') => Some(Cmd::RowBottom),
+                KeyCode::Char('c') => Some(Cmd::OpenFreqTable),
+                KeyCode::Char('H') => Some(Cmd::ToggleHistograms),
                 KeyCode::Char('F') | KeyCode::Char('f') => {
                     self.mode = Mode::Follow;
                     Some(Cmd::Redraw)
@@ -89,6 +154,43 @@ impl Prompt {
                     self.mode = Mode::Search(Dir::Reverse);
                     Some(Cmd::Redraw)
                 }
+                KeyCode::Char(':') => {
+                    self.input.clear();
+                    self.mode = Mode::Command;
+                    Some(Cmd::Redraw)
+                }
+                KeyCode::Char('&') => {
+                    self.input.clear();
+                    self.mode = Mode::Filter;
+                    Some(Cmd::Redraw)
+                }
+                KeyCode::Char('-') => {
+                    self.input.clear();
+                    self.mode = Mode::Option;
+                    Some(Cmd::Redraw)
+                }
+                KeyCode::Char('s') => Some(Cmd::CycleSort),
+                KeyCode::Char(' ') => Some(Cmd::ToggleHighlight),
+                KeyCode::Enter => {
+                    self.mode = Mode::Detail;
+                    Some(Cmd::OpenDetail)
+                }
+                KeyCode::Char('T') => Some(Cmd::Transpose),
+                KeyCode::Char('I') => {
+                    self.mode = Mode::Summary;
+                    Some(Cmd::OpenSummary)
+                }
+                KeyCode::Char('<') => Some(Cmd::MoveColumn(false)),
+                KeyCode::Char('>') => Some(Cmd::MoveColumn(true)),
+                KeyCode::Char('p') => Some(Cmd::TogglePin),
+                KeyCode::Char('x') => Some(Cmd::HideColumn),
+                KeyCode::Char('X') => Some(Cmd::UnhideColumns),
+                KeyCode::Char('C') => {
+                    self.mode = Mode::ColumnPicker;
+                    Some(Cmd::OpenColumnPicker)
+                }
+                KeyCode::Tab => Some(Cmd::NextTab),
+                KeyCode::BackTab => Some(Cmd::PrevTab),
                 KeyCode::Char('n') => Some(Cmd::SearchNext),
                 KeyCode::Char('N') => Some(Cmd::SearchPrev),
                 KeyCode::Char('g') => {
@@ -109,7 +211,7 @@ impl Prompt {
                 }
                 _ => None,
             },
-            Mode::Search(dir) => match key {
+            Mode::Search(_) | Mode::Filter | Mode::Command => match key {
                 KeyCode::Char(c) => {
                     self.input.push(c);
                     Some(Cmd::Redraw)
@@ -122,9 +224,15 @@ impl Prompt {
                     Some(Cmd::Redraw)
                 }
                 KeyCode::Enter => {
-                    let needle = std::mem::take(&mut self.input);
-                    self.mode = Mode::Normal;
-                    Some(Cmd::Search(needle, dir))
+                    let input = std::mem::take(&mut self.input);
+                    let mode = std::mem::take(&mut self.mode);
+                    match mode {
+                        Mode::Search(dir) => Some(Cmd::Search(input, dir)),
+                        Mode::Command => Some(Cmd::Command(input)),
+                        // An empty filter removes the last one
+                        _ if input.is_empty() => Some(Cmd::PopFilter),
+                        _ => Some(Cmd::Filter(input)),
+                    }
                 }
                 KeyCode::Esc => {
                     self.input.clear();
@@ -144,6 +252,45 @@ impl Prompt {
                 KeyCode::PageDown => None,
                 _ => None,
             },
+            Mode::Option => {
+                self.mode = Mode::Normal;
+                match key {
+                    KeyCode::Char('i') => Some(Cmd::ToggleCase(CaseMode::Smart)),
+                    KeyCode::Char('I') => Some(Cmd::ToggleCase(CaseMode::Insensitive)),
+                    KeyCode::Char('r') => Some(Cmd::ToggleRegex),
+                    _ => Some(Cmd::Redraw),
+                }
+            }
+            Mode::ColumnPicker => match key {
+                KeyCode::Down | KeyCode::Char('j') => Some(Cmd::PickerDown),
+                KeyCode::Up | KeyCode::Char('k') => Some(Cmd::PickerUp),
+                KeyCode::Char(' ') | KeyCode::Enter => Some(Cmd::PickerToggle),
+                KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('C') => {
+                    self.mode = Mode::Normal;
+                    Some(Cmd::ClosePicker)
+                }
+                _ => None,
+            },
+            Mode::Detail => match key {
+                KeyCode::Down | KeyCode::Char('j') => Some(Cmd::DetailDown),
+                KeyCode::Up | KeyCode::Char('k') => Some(Cmd::DetailUp),
+                KeyCode::PageDown | KeyCode::Char(' ') => Some(Cmd::DetailPgDown),
+                KeyCode::PageUp => Some(Cmd::DetailPgUp),
+                KeyCode::Esc | KeyCode::Char('q') | KeyCode::Enter => {
+                    self.mode = Mode::Normal;
+                    Some(Cmd::CloseDetail)
+                }
+                _ => None,
+            },
+            Mode::Summary => match key {
+                KeyCode::Right | KeyCode::Char('l') => Some(Cmd::ColRight),
+                KeyCode::Left | KeyCode::Char('h') => Some(Cmd::ColLeft),
+                KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('I') => {
+                    self.mode = Mode::Normal;
+                    Some(Cmd::CloseSummary)
+                }
+                _ => None,
+            },
             Mode::Follow => match key {
                 KeyCode::Right | KeyCode::Char('l') => Some(Cmd::ColRight),
                 KeyCode::Left | KeyCode::Char('h') => Some(Cmd::ColLeft),
@@ -157,8 +304,15 @@ impl Prompt {
     }
 
     pub fn handle_mouse(&mut self, ev: MouseEvent) -> Option<Cmd> {
+        match (&self.mode, ev.kind) {
+            (Mode::ColumnPicker | Mode::Summary, _) => return None,
+            (Mode::Detail, MouseEventKind::ScrollDown) => return Some(Cmd::DetailDown),
+            (Mode::Detail, MouseEventKind::ScrollUp) => return Some(Cmd::DetailUp),
+            (Mode::Detail, _) => return None,
+            _ => (),
+        }
         match ev.kind {
-            MouseEventKind::Down(MouseButton::Left) => Some(Cmd::ToggleHighlight(ev.row)),
+            MouseEventKind::Down(MouseButton::Left) => Some(Cmd::Click(ev.row)),
             MouseEventKind::ScrollDown => Some(Cmd::RowPgDown),
             MouseEventKind::ScrollUp => Some(Cmd::RowPgUp),
             MouseEventKind::ScrollLeft => Some(Cmd::ColLeft),
@@ -0,0 +1,385 @@
+use crate::prompt::Dir;
+use crate::view::RowMap;
+use regex::bytes::{Regex, RegexBuilder};
+use std::borrow::Cow;
+use std::ops::Range;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+#[derive(Clone, Copy, Default)]
+pub struct SearchOpts {
+    /// Interpret the pattern as a regular expression
+    pub regex: bool,
+    pub case: CaseMode,
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum CaseMode {
+    #[default]
+    Sensitive,
+    /// Ignore case, unless the pattern contains uppercase letters (like less's
+    /// `-i`)
+    Smart,
+    /// Always ignore case (like less's `-I`)
+    Insensitive,
+}
+
+/// A compiled search pattern
+#[derive(Clone)]
+pub struct Needle {
+    re: Regex,
+    /// The pattern, if it's a plain case-sensitive string
+    #[cfg(feature = "parquet")]
+    literal: Option<String>,
+    /// If set, only values in this column are searched
+    column: Option<String>,
+}
+
+impl Needle {
+    pub fn new(pattern: &str, opts: SearchOpts) -> anyhow::Result<Needle> {
+        let ignore_case = match opts.case {
+            CaseMode::Sensitive => false,
+            CaseMode::Smart => !has_uppercase(pattern, opts.regex),
+            CaseMode::Insensitive => true,
+        };
+        let re = if opts.regex {
+            Cow::Borrowed(pattern)
+        } else {
+            Cow::Owned(regex::escape(pattern))
+        };
+        let re = RegexBuilder::new(&re)
+            .case_insensitive(ignore_case)
+            .build()?;
+        #[cfg(feature = "parquet")]
+        let literal = (!opts.regex && !ignore_case).then(|| pattern.to_owned());
+        Ok(Needle {
+            re,
+            #[cfg(feature = "parquet")]
+            literal,
+            column: None,
+        })
+    }
+
+    /// Restricts the search to values in the given column
+    pub fn in_column(self, column: Option<String>) -> Needle {
+        Needle { column, ..self }
+    }
+
+    pub fn is_match(&self, txt: &[u8]) -> bool {
+        self.re.is_match(txt)
+    }
+
+    /// The byte ranges of the non-empty matches in `txt`
+    pub fn find_iter<'a>(&'a self, txt: &'a str) -> impl Iterator<Item = Range<usize>> + 'a {
+        self.re
+            .find_iter(txt.as_bytes())
+            .map(|m| m.range())
+            .filter(|r| {
+                !r.is_empty() && txt.is_char_boundary(r.start) && txt.is_char_boundary(r.end)
+            })
+    }
+
+    /// Returns the pattern if it's a plain string which must appear verbatim
+    /// in matching values.  This can be used to rule out values without
+    /// looking at them.
+    #[cfg(feature = "parquet")]
+    pub fn literal(&self) -> Option<&str> {
+        self.literal.as_deref()
+    }
+
+    pub fn column(&self) -> Option<&str> {
+        self.column.as_deref()
+    }
+}
+
+/// Whether the pattern contains any uppercase letters.  In a regex, escape
+/// sequences like `\W` don't count.
+fn has_uppercase(pattern: &str, regex: bool) -> bool {
+    let mut escaped = false;
+    for c in pattern.chars() {
+        if escaped {
+            escaped = false;
+        } else if regex && c == '\\' {
+            escaped = true;
+        } else if c.is_uppercase() {
+            return true;
+        }
+    }
+    false
+}
+
+/// A search which can be run on a background thread.  It should push matching
+/// rows to the `Progress` in order, and stop early if the search is cancelled.
+pub type SearchJob = Box<dyn FnOnce(&mut Progress) -> anyhow::Result<()> + Send>;
+
+/// Used by search jobs to send their results back to the UI thread
+pub struct Progress {
+    tx: Sender<Msg>,
+    cancelled: Arc<AtomicBool>,
+    new_matches: Vec<usize>,
+    rows_scanned: usize,
+    last_sent: Instant,
+    /// Added to the rows reported by the job.  See `run_part()`.
+    offset: usize,
+}
+
+enum Msg {
+    Progress {
+        new_matches: Vec<usize>,
+        rows_scanned: usize,
+    },
+    Done(anyhow::Result<()>),
+}
+
+impl Progress {
+    /// Records a matching row.  Rows must be pushed in ascending order.
+    pub fn push(&mut self, row: usize) {
+        self.new_matches.push(self.offset + row);
+    }
+
+    /// Records that all rows before `rows_scanned` have been searched.  Returns
+    /// false if the search has been cancelled, in which case the job should
+    /// stop.
+    pub fn update(&mut self, rows_scanned: usize) -> bool {
+        self.rows_scanned = self.offset + rows_scanned;
+        if self.last_sent.elapsed() > Duration::from_millis(50) {
+            self.send();
+        }
+        !self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Runs another job as part of this one.  The rows it reports are
+    /// relative to `first_row`.
+    pub fn run_part(&mut self, first_row: usize, job: SearchJob) -> anyhow::Result<()> {
+        let offset = self.offset;
+        self.offset += first_row;
+        let result = job(self);
+        self.offset = offset;
+        result
+    }
+
+    fn send(&mut self) {
+        let msg = Msg::Progress {
+            new_matches: std::mem::take(&mut self.new_matches),
+            rows_scanned: self.rows_scanned,
+        };
+        if self.tx.send(msg).is_err() {
+            // The UI thread has lost interest
+            self.cancelled.store(true, Ordering::Relaxed);
+        }
+        self.last_sent = Instant::now();
+    }
+}
+
+/// A search running on a background thread
+pub struct Search {
+    /// The rows which contain the needle, in ascending order.  This grows as
+    /// the search progresses.
+    pub matches: Vec<usize>,
+    pub needle: Needle,
+    pub dir: Dir,
+    /// If the view is sorted or filtered, the job's results need to be mapped
+    /// to positions in the view
+    view: Option<Arc<RowMap>>,
+    rows_scanned: usize,
+    total_rows: usize,
+    state: State,
+    rx: Receiver<Msg>,
+    cancelled: Arc<AtomicBool>,
+}
+
+#[derive(PartialEq)]
+enum State {
+    Running,
+    Done,
+    Cancelled,
+    Failed,
+}
+
+impl Search {
+    pub fn spawn(
+        job: SearchJob,
+        needle: Needle,
+        dir: Dir,
+        total_rows: usize,
+        view: Option<Arc<RowMap>>,
+    ) -> Search {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let mut progress = Progress {
+            tx,
+            cancelled: cancelled.clone(),
+            new_matches: vec![],
+            rows_scanned: 0,
+            last_sent: Instant::now(),
+            offset: 0,
+        };
+        std::thread::spawn(move || {
+            let start = Instant::now();
+            let result = job(&mut progress);
+            debug!(took = ?start.elapsed(), "Search finished");
+            progress.send();
+            let _ = progress.tx.send(Msg::Done(result));
+        });
+        Search {
+            matches: vec![],
+            needle,
+            dir,
+            view,
+            rows_scanned: 0,
+            total_rows,
+            state: State::Running,
+            rx,
+            cancelled,
+        }
+    }
+
+    /// Collects any new results from the background thread.  Returns true if
+    /// anything changed.
+    pub fn poll(&mut self) -> bool {
+        let mut changed = false;
+        loop {
+            match self.rx.try_recv() {
+                Ok(Msg::Progress {
+                    new_matches,
+                    rows_scanned,
+                }) => {
+                    match &self.view {
+                        Some(view) => {
+                            for row in new_matches {
+                                match view.position(row) {
+                                    Ok(Some(x)) => self.matches.push(x),
+                                    // Filtered out
+                                    Ok(None) => (),
+                                    // Rows added since the view was sorted
+                                    // aren't in the view
+                                    Err(e) => debug!("{e}"),
+                                }
+                            }
+                            self.matches.sort_unstable();
+                        }
+                        None => self.matches.extend(new_matches),
+                    }
+                    self.rows_scanned = rows_scanned;
+                }
+                Ok(Msg::Done(result)) if self.state == State::Running => match result {
+                    Ok(()) => {
+                        self.state = State::Done;
+                        self.rows_scanned = self.total_rows;
+                    }
+                    Err(e) => {
+                        warn!("Search failed: {e}");
+                        self.state = State::Failed;
+                    }
+                },
+                Ok(Msg::Done(_)) => (),
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => return changed,
+            }
+            changed = true;
+        }
+    }
+
+    pub fn cancel(&mut self) {
+        if self.state == State::Running {
+            self.cancelled.store(true, Ordering::Relaxed);
+            self.state = State::Cancelled;
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.state == State::Running
+    }
+
+    /// Whether the row is known to contain a match
+    pub fn is_match(&self, row: usize) -> bool {
+        self.matches.binary_search(&row).is_ok()
+    }
+
+    /// Whether every row before `row` has been searched
+    pub fn has_scanned(&self, row: usize) -> bool {
+        // In a sorted or filtered view, the rows aren't scanned in order
+        !self.is_running() || (self.view.is_none() && self.rows_scanned >= row)
+    }
+
+    /// A short description of the search for the footer
+    pub fn status(&self) -> String {
+        let n = self.matches.len();
+        match self.state {
+            State::Running => {
+                let pct = self.rows_scanned * 100 / self.total_rows.max(1);
+                format!("({n} matches, {pct}%)")
+            }
+            State::Done if n == 0 => String::new(),
+            State::Done => format!("({n} matches)"),
+            State::Cancelled => format!("({n} matches, cancelled)"),
+            State::Failed => "(search failed)".to_owned(),
+        }
+    }
+
+    /// The nearest match to the current row, in the given direction
+    pub fn next_match(&self, current_row: usize, dir: Dir) -> Option<usize> {
+        match dir {
+            Dir::Forward => {
+                let idx = self.matches.partition_point(|x| *x <= current_row);
+                self.matches.get(idx).copied()
+            }
+            Dir::Reverse => {
+                let idx = self.matches.partition_point(|x| *x < current_row);
+                idx.checked_sub(1).map(|idx| self.matches[idx])
+            }
+        }
+    }
+}
+
+/// Runs the job on this thread, and returns the rows it matched
+#[cfg(test)]
+pub fn run_job(job: SearchJob) -> anyhow::Result<Vec<usize>> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut progress = Progress {
+        tx,
+        cancelled: Arc::new(AtomicBool::new(false)),
+        new_matches: vec![],
+        rows_scanned: 0,
+        last_sent: Instant::now(),
+        offset: 0,
+    };
+    job(&mut progress)?;
+    progress.send();
+    drop(progress);
+    let mut matches = vec![];
+    for msg in rx {
+        if let Msg::Progress { new_matches, .. } = msg {
+            matches.extend(new_matches);
+        }
+    }
+    Ok(matches)
+}
+
+impl Drop for Search {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smart_case() -> anyhow::Result<()> {
+        let opts = SearchOpts {
+            regex: true,
+            case: CaseMode::Smart,
+        };
+        let needle = Needle::new(r"err\d", opts)?;
+        assert!(needle.is_match(b"ERR1"));
+        assert!(needle.is_match(b"err2"));
+        let needle = Needle::new(r"Err\d", opts)?;
+        assert!(!needle.is_match(b"ERR1"));
+        assert!(needle.is_match(b"Err1"));
+        Ok(())
+    }
+}
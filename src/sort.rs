@@ -0,0 +1,337 @@
+use anyhow::ensure;
+use arrow::array::{ArrayRef, UInt64Array};
+use arrow::compute::SortOptions;
+use arrow::datatypes::DataType;
+use arrow::row::{RowConverter, Rows, SortField};
+use fileslice::FileSlice;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, Write};
+use std::ops::Range;
+use std::sync::Arc;
+use tracing::debug;
+
+/// How many rows to sort in memory at once.  Bigger inputs are sorted in runs
+/// of this size, which are spilled to disk and then merged.
+const RUN_SIZE: usize = 4_000_000;
+
+/// The order in which to show the rows of a sorted view
+pub struct Permutation {
+    /// The nth row of the view is row `forward[n]` of the source
+    forward: Values,
+    /// The nth row of the source is row `inverse[n]` of the view
+    inverse: Values,
+}
+
+impl Permutation {
+    /// Builds the permutation which sorts the rows by the given key column.
+    /// The keys should be provided in row order, in as many chunks as you like.
+    /// The sort is stable, and nulls go last.
+    pub fn build(
+        keys: impl Iterator<Item = anyhow::Result<ArrayRef>>,
+        dtype: &DataType,
+        descending: bool,
+    ) -> anyhow::Result<Permutation> {
+        Permutation::build_with_run_size(keys, dtype, descending, RUN_SIZE)
+    }
+
+    fn build_with_run_size(
+        keys: impl Iterator<Item = anyhow::Result<ArrayRef>>,
+        dtype: &DataType,
+        descending: bool,
+        run_size: usize,
+    ) -> anyhow::Result<Permutation> {
+        let mut sorter = Sorter::new(dtype, descending, run_size)?;
+        for chunk in keys {
+            sorter.push(&chunk?)?;
+        }
+        let forward = sorter.finish()?;
+        let inverse = match &forward {
+            Values::Memory(xs) => {
+                let mut inverse = vec![0; xs.len()];
+                for (i, x) in xs.iter().enumerate() {
+                    inverse[*x as usize] = i as u64;
+                }
+                Values::Memory(inverse)
+            }
+            Values::Disk { len, .. } => {
+                // Sorting the view indices by source index gives the inverse
+                let mut sorter = Sorter::new(&DataType::UInt64, false, run_size)?;
+                for start in (0..*len).step_by(run_size) {
+                    let chunk = forward.get_range(start..(start + run_size).min(*len))?;
+                    sorter.push(&(Arc::new(UInt64Array::from(chunk)) as ArrayRef))?;
+                }
+                sorter.finish()?
+            }
+        };
+        Ok(Permutation { forward, inverse })
+    }
+
+    pub fn len(&self) -> usize {
+        self.forward.len()
+    }
+
+    /// The source rows for the given rows of the view
+    pub fn get_range(&self, rows: Range<usize>) -> anyhow::Result<Vec<usize>> {
+        let xs = self.forward.get_range(rows)?;
+        Ok(xs.into_iter().map(|x| x as usize).collect())
+    }
+
+    /// The row of the view at which the given source row appears
+    pub fn position(&self, source_row: usize) -> anyhow::Result<usize> {
+        let xs = self.inverse.get_range(source_row..source_row + 1)?;
+        Ok(xs[0] as usize)
+    }
+}
+
+/// A column of row indices, either in memory or in a tempfile
+pub enum Values {
+    Memory(Vec<u64>),
+    /// Little-endian u64s
+    Disk {
+        fs: FileSlice,
+        len: usize,
+    },
+}
+
+impl Values {
+    pub fn len(&self) -> usize {
+        match self {
+            Values::Memory(xs) => xs.len(),
+            Values::Disk { len, .. } => *len,
+        }
+    }
+
+    pub fn get_range(&self, range: Range<usize>) -> anyhow::Result<Vec<u64>> {
+        ensure!(
+            range.end <= self.len(),
+            "Row {} is out of range",
+            range.end - 1
+        );
+        match self {
+            Values::Memory(xs) => Ok(xs[range].to_vec()),
+            Values::Disk { fs, .. } => {
+                let mut buf = vec![0; range.len() * 8];
+                fs.slice(range.start as u64 * 8..range.end as u64 * 8)
+                    .read_exact(&mut buf)?;
+                Ok(buf
+                    .chunks_exact(8)
+                    .map(|x| u64::from_le_bytes(x.try_into().unwrap()))
+                    .collect())
+            }
+        }
+    }
+
+    /// Searches ascending values for `x`, like `slice::binary_search()`
+    pub fn binary_search(&self, x: u64) -> anyhow::Result<Result<usize, usize>> {
+        match self {
+            Values::Memory(xs) => Ok(xs.binary_search(&x)),
+            Values::Disk { len, .. } => {
+                let (mut lo, mut hi) = (0, *len);
+                while lo < hi {
+                    let mid = lo + (hi - lo) / 2;
+                    match self.get_range(mid..mid + 1)?[0].cmp(&x) {
+                        std::cmp::Ordering::Less => lo = mid + 1,
+                        std::cmp::Ordering::Greater => hi = mid,
+                        std::cmp::Ordering::Equal => return Ok(Ok(mid)),
+                    }
+                }
+                Ok(Err(lo))
+            }
+        }
+    }
+}
+
+/// Collects row indices into `Values`.  Once there are more than `RUN_SIZE`
+/// of them, they're written to a tempfile.
+#[derive(Default)]
+pub struct ValuesBuilder {
+    buf: Vec<u64>,
+    spilled: Option<(BufWriter<File>, usize)>,
+}
+
+impl ValuesBuilder {
+    pub fn push(&mut self, x: u64) -> anyhow::Result<()> {
+        self.buf.push(x);
+        if self.buf.len() >= RUN_SIZE {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    fn spill(&mut self) -> anyhow::Result<()> {
+        let (wtr, len) = match &mut self.spilled {
+            Some(x) => x,
+            None => self
+                .spilled
+                .insert((BufWriter::new(tempfile::tempfile()?), 0)),
+        };
+        for x in self.buf.drain(..) {
+            wtr.write_all(&x.to_le_bytes())?;
+            *len += 1;
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> anyhow::Result<Values> {
+        if self.spilled.is_none() {
+            return Ok(Values::Memory(self.buf));
+        }
+        self.spill()?;
+        let (wtr, len) = self.spilled.unwrap();
+        let file = wtr.into_inner().map_err(|e| e.into_error())?;
+        Ok(Values::Disk {
+            fs: FileSlice::new(file),
+            len,
+        })
+    }
+}
+
+/// Sorts row indices by key.  Keys are converted to arrow's row format, which
+/// can be compared bytewise, so spilled runs can be merged without knowing
+/// anything about the key type.
+struct Sorter {
+    converter: RowConverter,
+    /// The keys of the current run
+    rows: Rows,
+    /// The row index of the first key in the current run
+    run_start: u64,
+    /// Sorted runs which have been spilled to disk
+    runs: Vec<File>,
+    run_size: usize,
+}
+
+impl Sorter {
+    fn new(dtype: &DataType, descending: bool, run_size: usize) -> anyhow::Result<Sorter> {
+        let opts = SortOptions {
+            descending,
+            nulls_first: false,
+        };
+        let converter = RowConverter::new(vec![SortField::new_with_options(dtype.clone(), opts)])?;
+        Ok(Sorter {
+            rows: converter.empty_rows(0, 0),
+            converter,
+            run_start: 0,
+            runs: vec![],
+            run_size,
+        })
+    }
+
+    fn push(&mut self, keys: &ArrayRef) -> anyhow::Result<()> {
+        self.converter
+            .append(&mut self.rows, std::slice::from_ref(keys))?;
+        if self.rows.num_rows() >= self.run_size {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// The indices of the current run, in sorted order
+    fn sorted_run(&self) -> Vec<usize> {
+        let mut idxs = (0..self.rows.num_rows()).collect::<Vec<_>>();
+        // Stable, so equal keys stay in row order
+        idxs.sort_by_key(|i| self.rows.row(*i));
+        idxs
+    }
+
+    /// Writes the current run to disk as (key length, key, row index) records
+    fn spill(&mut self) -> anyhow::Result<()> {
+        let mut wtr = BufWriter::new(tempfile::tempfile()?);
+        for i in self.sorted_run() {
+            let key = self.rows.row(i);
+            let key = key.as_ref();
+            wtr.write_all(&(key.len() as u32).to_le_bytes())?;
+            wtr.write_all(key)?;
+            wtr.write_all(&(self.run_start + i as u64).to_le_bytes())?;
+        }
+        let mut file = wtr.into_inner().map_err(|e| e.into_error())?;
+        file.rewind()?;
+        debug!(n_rows = self.rows.num_rows(), "Spilled a sorted run");
+        self.runs.push(file);
+        self.run_start += self.rows.num_rows() as u64;
+        self.rows = self.converter.empty_rows(0, 0);
+        Ok(())
+    }
+
+    fn finish(mut self) -> anyhow::Result<Values> {
+        if self.runs.is_empty() {
+            let run_start = self.run_start;
+            let xs = self.sorted_run();
+            return Ok(Values::Memory(
+                xs.into_iter().map(|i| run_start + i as u64).collect(),
+            ));
+        }
+        if self.rows.num_rows() > 0 {
+            self.spill()?;
+        }
+
+        // Merge the runs
+        let mut runs = self
+            .runs
+            .into_iter()
+            .map(BufReader::new)
+            .collect::<Vec<_>>();
+        let mut heap = BinaryHeap::new();
+        for (run_idx, run) in runs.iter_mut().enumerate() {
+            if let Some((key, row)) = read_record(run)? {
+                heap.push(Reverse((key, row, run_idx)));
+            }
+        }
+        let mut wtr = BufWriter::new(tempfile::tempfile()?);
+        let mut len = 0;
+        while let Some(Reverse((_, row, run_idx))) = heap.pop() {
+            wtr.write_all(&row.to_le_bytes())?;
+            len += 1;
+            if let Some((key, row)) = read_record(&mut runs[run_idx])? {
+                heap.push(Reverse((key, row, run_idx)));
+            }
+        }
+        let file = wtr.into_inner().map_err(|e| e.into_error())?;
+        debug!(len, n_runs = runs.len(), "Merged the sorted runs");
+        Ok(Values::Disk {
+            fs: FileSlice::new(file),
+            len,
+        })
+    }
+}
+
+fn read_record(rdr: &mut impl Read) -> anyhow::Result<Option<(Vec<u8>, u64)>> {
+    let mut len = [0; 4];
+    match rdr.read_exact(&mut len) {
+        Ok(()) => (),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let mut key = vec![0; u32::from_le_bytes(len) as usize];
+    rdr.read_exact(&mut key)?;
+    let mut row = [0; 8];
+    rdr.read_exact(&mut row)?;
+    Ok(Some((key, u64::from_le_bytes(row))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Int32Array;
+
+    #[test]
+    fn spilled_sort_matches_in_memory_sort() -> anyhow::Result<()> {
+        let data = [5, 3, 9, 3, 1, 7, 3, 8, 2, 6];
+        let chunks = || {
+            data.chunks(4)
+                .map(|x| Ok(Arc::new(Int32Array::from(x.to_vec())) as ArrayRef))
+        };
+        let in_memory = Permutation::build(chunks(), &DataType::Int32, false)?;
+        let spilled = Permutation::build_with_run_size(chunks(), &DataType::Int32, false, 3)?;
+        assert!(matches!(spilled.forward, Values::Disk { .. }));
+        let expected = [4, 8, 1, 3, 6, 0, 9, 5, 7, 2];
+        assert_eq!(in_memory.get_range(0..10)?, expected);
+        assert_eq!(spilled.get_range(0..10)?, expected);
+        for (view_row, source_row) in expected.into_iter().enumerate() {
+            assert_eq!(in_memory.position(source_row)?, view_row);
+            assert_eq!(spilled.position(source_row)?, view_row);
+        }
+        Ok(())
+    }
+}
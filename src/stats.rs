@@ -1,8 +1,14 @@
 use crate::draw::RenderSettings;
 use arrow::{
-    array::{Array, GenericBinaryArray, GenericStringArray, OffsetSizeTrait, PrimitiveArray},
+    array::{
+        Array, AsArray, GenericBinaryArray, GenericStringArray, OffsetSizeTrait, PrimitiveArray,
+    },
     datatypes::*,
 };
+use std::collections::HashMap;
+
+/// The number of buckets in a numeric histogram
+pub const HISTOGRAM_BUCKETS: usize = 64;
 
 #[derive(Debug, Clone)]
 pub struct ColumnStats {
@@ -11,6 +17,19 @@ pub struct ColumnStats {
     pub ideal_width: u16,
     /// `None` means "more than 255"
     pub cardinality: Option<u8>,
+    /// For numeric and timestamp columns, and columns with few distinct values.
+    /// When merging, each row should only be counted once.
+    pub distribution: Option<Distribution>,
+}
+
+/// The shape of a column's values, for drawing a histogram
+#[derive(Debug, Clone)]
+pub enum Distribution {
+    /// How many values fall in each of `HISTOGRAM_BUCKETS` equal-width buckets
+    /// spanning `range`
+    Numeric { range: MinMax, counts: Vec<u64> },
+    /// How often each value appears
+    Categorical(HashMap<String, u64>),
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -35,6 +54,85 @@ impl ColumnStats {
             .cardinality
             .zip(other.cardinality)
             .map(|(x, y)| x.max(y));
+        self.distribution = match (self.distribution.take(), other.distribution) {
+            (Some(x), Some(y)) => x.merge(y),
+            // A numeric chunk has no histogram if it's all nulls...
+            (x @ Some(Distribution::Numeric { .. }), None)
+            | (None, x @ Some(Distribution::Numeric { .. })) => x,
+            // ...but a categorical chunk has no histogram if it has too many
+            // values
+            _ => None,
+        };
+    }
+}
+
+impl Distribution {
+    fn numeric(values: &[f64]) -> Option<Distribution> {
+        let values = || values.iter().copied().filter(|x| !x.is_nan());
+        let min = values().reduce(f64::min)?;
+        let max = values().reduce(f64::max)?;
+        let range = MinMax { min, max };
+        let mut counts = vec![0; HISTOGRAM_BUCKETS];
+        for x in values() {
+            counts[range.bucket(x)] += 1;
+        }
+        Some(Distribution::Numeric { range, counts })
+    }
+
+    fn merge(self, other: Distribution) -> Option<Distribution> {
+        match (self, other) {
+            (
+                Distribution::Numeric {
+                    range: r1,
+                    counts: c1,
+                },
+                Distribution::Numeric {
+                    range: r2,
+                    counts: c2,
+                },
+            ) => {
+                // Histograms which already span the combined range are added
+                // as-is.  Others are re-bucketed, assuming that values are at
+                // the centre of their bucket.  This loses some precision, so
+                // we avoid doing it more than necessary.
+                let range = MinMax {
+                    min: r1.min.min(r2.min),
+                    max: r1.max.max(r2.max),
+                };
+                let mut counts = vec![0; HISTOGRAM_BUCKETS];
+                for (r, c) in [(r1, c1), (r2, c2)] {
+                    if r.min == range.min && r.max == range.max {
+                        counts.iter_mut().zip(c).for_each(|(x, n)| *x += n);
+                        continue;
+                    }
+                    let bucket_width = (r.max - r.min) / HISTOGRAM_BUCKETS as f64;
+                    for (i, n) in c.into_iter().enumerate() {
+                        let centre = r.min + (i as f64 + 0.5) * bucket_width;
+                        counts[range.bucket(centre)] += n;
+                    }
+                }
+                Some(Distribution::Numeric { range, counts })
+            }
+            (Distribution::Categorical(mut x), Distribution::Categorical(y)) => {
+                for (val, n) in y {
+                    *x.entry(val).or_default() += n;
+                }
+                (x.len() <= u8::MAX as usize).then_some(Distribution::Categorical(x))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl MinMax {
+    /// The histogram bucket which `x` falls into
+    fn bucket(&self, x: f64) -> usize {
+        let width = self.max - self.min;
+        if width <= 0.0 {
+            return HISTOGRAM_BUCKETS / 2;
+        }
+        let idx = ((x - self.min) / width * HISTOGRAM_BUCKETS as f64) as usize;
+        idx.min(HISTOGRAM_BUCKETS - 1)
     }
 }
 
@@ -85,17 +183,20 @@ impl ColumnStats {
                 TimeUnit::Microsecond => 8 + 1 + 6, // HH:MM:SS.mmmuuu
                 TimeUnit::Nanosecond => 8 + 1 + 9,  // HH:MM:SS.mmmuuunnn
             }),
-            DataType::Timestamp(unit, tz) => ColumnStats::fixed_len(
-                20 + match unit {
-                    TimeUnit::Second => 0,
-                    TimeUnit::Millisecond => 3 + 1,
-                    TimeUnit::Microsecond => 6 + 1,
-                    TimeUnit::Nanosecond => 9 + 1,
-                } + tz
-                    .as_ref()
-                    .map(|tz| tz.to_string().len() as u16)
-                    .unwrap_or(0),
-            ),
+            DataType::Timestamp(unit, tz) => ColumnStats {
+                distribution: ColumnStats::timestamp_distribution(col)?,
+                ..ColumnStats::fixed_len(
+                    20 + match unit {
+                        TimeUnit::Second => 0,
+                        TimeUnit::Millisecond => 3 + 1,
+                        TimeUnit::Microsecond => 6 + 1,
+                        TimeUnit::Nanosecond => 9 + 1,
+                    } + tz
+                        .as_ref()
+                        .map(|tz| tz.to_string().len() as u16)
+                        .unwrap_or(0),
+                )
+            },
             DataType::Duration(_) => ColumnStats::fallback(col)?, // TODO
             DataType::Interval(_) => ColumnStats::fallback(col)?, // TODO
 
@@ -134,6 +235,11 @@ impl ColumnStats {
             .chain(max.map(len))
             .max()
             .unwrap_or(0);
+        let values = col
+            .iter()
+            .flatten()
+            .map(|x| x.into() as f64)
+            .collect::<Vec<_>>();
         Ok(ColumnStats {
             min_max: min.zip(max).map(|(min, max)| MinMax {
                 min: min as f64,
@@ -141,6 +247,7 @@ impl ColumnStats {
             }),
             ideal_width: max_len,
             cardinality: None,
+            distribution: Distribution::numeric(&values),
         })
     }
 
@@ -167,10 +274,12 @@ impl ColumnStats {
             .chain(max.map(len))
             .max()
             .unwrap_or(0);
+        let values = col.iter().flatten().map(Into::into).collect::<Vec<_>>();
         Ok(ColumnStats {
             min_max: min.zip(max).map(|(min, max)| MinMax { min, max }),
             ideal_width: max_len,
             cardinality: None,
+            distribution: Distribution::numeric(&values),
         })
     }
 
@@ -190,12 +299,21 @@ impl ColumnStats {
         };
 
         // TODO: Use the dictionary.  Don't colour columns with no dictionary
-        let unique_vals: std::collections::HashSet<&str> = col.iter().flatten().collect();
+        let mut counts: HashMap<&str, u64> = HashMap::new();
+        for val in col.iter().flatten() {
+            *counts.entry(val).or_default() += 1;
+        }
+        let cardinality = u8::try_from(counts.len()).ok();
+        let distribution = cardinality.map(|_| {
+            let counts = counts.into_iter().map(|(x, n)| (x.to_owned(), n));
+            Distribution::Categorical(counts.collect())
+        });
 
         Ok(ColumnStats {
             min_max: None,
             ideal_width: max_len,
-            cardinality: u8::try_from(unique_vals.len()).ok(),
+            cardinality,
+            distribution,
         })
     }
 
@@ -217,14 +335,27 @@ impl ColumnStats {
             min_max: None,
             ideal_width: max_len,
             cardinality: None,
+            distribution: None,
         })
     }
 
+    fn timestamp_distribution(col: &dyn Array) -> anyhow::Result<Option<Distribution>> {
+        let col = arrow::compute::cast(col, &DataType::Int64)?;
+        let values = col
+            .as_primitive::<Int64Type>()
+            .iter()
+            .flatten()
+            .map(|x| x as f64)
+            .collect::<Vec<_>>();
+        Ok(Distribution::numeric(&values))
+    }
+
     fn fixed_len(max_len: u16) -> ColumnStats {
         ColumnStats {
             ideal_width: max_len,
             min_max: None,
             cardinality: None,
+            distribution: None,
         }
     }
 
@@ -233,6 +364,7 @@ impl ColumnStats {
             ideal_width: column_width(col)? as u16,
             min_max: None,
             cardinality: None,
+            distribution: None,
         })
     }
 }
@@ -264,3 +396,49 @@ fn fmt_len(x: impl std::fmt::Display) -> anyhow::Result<usize> {
     write!(counter, "{}", x)?;
     Ok(counter.0)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Float64Array, StringArray};
+
+    #[test]
+    fn distributions_merge() -> anyhow::Result<()> {
+        let settings = RenderSettings::default();
+        let floats = |xs: Vec<f64>| ColumnStats::new("x", &Float64Array::from(xs), &settings);
+        let mut stats = floats(vec![0.0, 1.0, 1.0])?;
+        stats.merge(floats(vec![3.0, 4.0])?);
+        let Some(Distribution::Numeric { range, counts }) = &stats.distribution else {
+            panic!("Expected a numeric distribution");
+        };
+        assert_eq!((range.min, range.max), (0.0, 4.0));
+        assert_eq!(counts.iter().sum::<u64>(), 5);
+        assert_eq!(counts[0], 1);
+        assert_eq!(counts[HISTOGRAM_BUCKETS - 1], 1);
+        // A chunk within the range is added bucket-for-bucket
+        let before = counts.clone();
+        stats.merge(floats(vec![0.0, 2.0, 4.0])?);
+        let Some(Distribution::Numeric { counts, .. }) = &stats.distribution else {
+            panic!("Expected a numeric distribution");
+        };
+        assert_eq!(counts.iter().sum::<u64>(), 8);
+        assert_eq!(counts[0], before[0] + 1);
+        assert_eq!(
+            counts[HISTOGRAM_BUCKETS / 2],
+            before[HISTOGRAM_BUCKETS / 2] + 1
+        );
+        assert_eq!(
+            counts[HISTOGRAM_BUCKETS - 1],
+            before[HISTOGRAM_BUCKETS - 1] + 1
+        );
+
+        let strings = |xs: Vec<&str>| ColumnStats::new("x", &StringArray::from(xs), &settings);
+        let mut stats = strings(vec!["a", "b", "a"])?;
+        stats.merge(strings(vec!["b", "c"])?);
+        let Some(Distribution::Categorical(counts)) = &stats.distribution else {
+            panic!("Expected a categorical distribution");
+        };
+        assert_eq!((counts["a"], counts["b"], counts["c"]), (2, 2, 1));
+        Ok(())
+    }
+}
@@ -0,0 +1,427 @@
+use crate::draw::RenderSettings;
+use crate::format::CellFormatter;
+use arrow::array::{Array, ArrayRef, AsArray};
+use arrow::datatypes::{DataType, Decimal128Type, Float64Type};
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// Distinct values are counted exactly until there are this many of them
+const DISTINCT_LIMIT: usize = 100_000;
+/// Quantiles are estimated from a sample of this many values
+const SAMPLE_SIZE: usize = 100_000;
+/// How many of the most frequent values to show
+const TOP_N: usize = 10;
+pub const QUANTILES: [f64; 5] = [0.05, 0.25, 0.5, 0.75, 0.95];
+
+/// Summary statistics for a column.  Values are formatted the same way they're
+/// rendered in the grid.
+#[derive(Clone, Default)]
+pub struct Summary {
+    pub count: usize,
+    pub nulls: usize,
+    pub distinct: usize,
+    /// If false, `distinct` is a HyperLogLog estimate
+    pub distinct_is_exact: bool,
+    pub min: Option<String>,
+    pub max: Option<String>,
+    /// Only for numeric columns
+    pub mean: Option<f64>,
+    pub stddev: Option<f64>,
+    /// One per entry in `QUANTILES`.  Only for numeric columns.
+    pub quantiles: Vec<String>,
+    /// If false, the quantiles were estimated from a sample
+    pub quantiles_are_exact: bool,
+    /// The most frequent values, with their counts
+    pub top: Vec<(String, usize)>,
+    /// If false, values which first appeared after the distinct limit was
+    /// reached weren't counted
+    pub top_is_exact: bool,
+}
+
+/// Summary statistics which are being computed on a background thread
+pub struct ColumnSummary {
+    pub column: String,
+    pub dtype: DataType,
+    /// The latest results.  These are partial until the job finishes.
+    pub summary: Summary,
+    total_rows: usize,
+    state: State,
+    rx: Receiver<Msg>,
+}
+
+#[derive(PartialEq)]
+enum State {
+    Running,
+    Done,
+    Failed,
+}
+
+enum Msg {
+    Progress(Summary),
+    Done(anyhow::Result<Summary>),
+}
+
+impl ColumnSummary {
+    /// `values` should yield every value of the column, cast to the type it's
+    /// displayed as.  The job stops when the `ColumnSummary` is dropped.
+    pub fn spawn(
+        column: String,
+        dtype: DataType,
+        values: impl Iterator<Item = anyhow::Result<ArrayRef>> + Send + 'static,
+        total_rows: usize,
+        settings: RenderSettings,
+    ) -> ColumnSummary {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let acc_dtype = dtype.clone();
+        std::thread::spawn(move || {
+            let start = Instant::now();
+            let mut last_sent = Instant::now();
+            let mut acc = Accumulator::new(&acc_dtype, settings);
+            let result = (|| {
+                for col in values {
+                    acc.add(col?.as_ref())?;
+                    if last_sent.elapsed() > Duration::from_millis(200) {
+                        if tx.send(Msg::Progress(acc.summary())).is_err() {
+                            // The UI thread has lost interest
+                            return Ok(None);
+                        }
+                        last_sent = Instant::now();
+                    }
+                }
+                Ok(Some(acc.summary()))
+            })();
+            debug!(took = ?start.elapsed(), "Summary finished");
+            if let Some(result) = result.transpose() {
+                let _ = tx.send(Msg::Done(result));
+            }
+        });
+        ColumnSummary {
+            column,
+            dtype,
+            summary: Summary::default(),
+            total_rows,
+            state: State::Running,
+            rx,
+        }
+    }
+
+    /// Collects any new results from the background thread.  Returns true if
+    /// anything changed.
+    pub fn poll(&mut self) -> bool {
+        let mut changed = false;
+        loop {
+            match self.rx.try_recv() {
+                Ok(Msg::Progress(summary)) => self.summary = summary,
+                Ok(Msg::Done(Ok(summary))) => {
+                    self.summary = summary;
+                    self.state = State::Done;
+                }
+                Ok(Msg::Done(Err(e))) => {
+                    warn!("Summary failed: {e}");
+                    self.state = State::Failed;
+                }
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => return changed,
+            }
+            changed = true;
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.state == State::Running
+    }
+
+    /// A short description of the job's progress for the footer
+    pub fn status(&self) -> String {
+        match self.state {
+            State::Running => {
+                let pct = self.summary.count * 100 / self.total_rows.max(1);
+                format!("(scanning, {pct}%)")
+            }
+            State::Done => String::new(),
+            State::Failed => "(failed)".to_owned(),
+        }
+    }
+}
+
+struct Accumulator {
+    settings: RenderSettings,
+    /// Numeric columns are also cast to floats, for the mean etc.
+    numeric: bool,
+    integral: bool,
+    count: usize,
+    nulls: usize,
+    counts: HashMap<String, usize>,
+    /// Whether `counts` is missing some values
+    overflowed: bool,
+    hll: HyperLogLog,
+    /// Only for non-numeric columns; numeric ones use `min_num` and `max_num`
+    min: Option<String>,
+    max: Option<String>,
+    // Welford's online algorithm
+    n: usize,
+    mean: f64,
+    m2: f64,
+    min_num: f64,
+    max_num: f64,
+    /// Integer columns keep their exact extremes, since large values don't
+    /// survive the trip through `f64`
+    min_int: Option<i128>,
+    max_int: Option<i128>,
+    /// A uniform sample of the numeric values (reservoir sampling)
+    sample: Vec<f64>,
+    rng: u64,
+}
+
+impl Accumulator {
+    fn new(dtype: &DataType, settings: RenderSettings) -> Accumulator {
+        Accumulator {
+            settings,
+            numeric: dtype.is_numeric(),
+            integral: dtype.is_integer(),
+            count: 0,
+            nulls: 0,
+            counts: HashMap::new(),
+            overflowed: false,
+            hll: HyperLogLog::default(),
+            min: None,
+            max: None,
+            n: 0,
+            mean: 0.0,
+            m2: 0.0,
+            min_num: f64::INFINITY,
+            max_num: f64::NEG_INFINITY,
+            min_int: None,
+            max_int: None,
+            sample: vec![],
+            rng: 0x2545_f491_4f6c_dd1d,
+        }
+    }
+
+    fn add(&mut self, col: &dyn Array) -> anyhow::Result<()> {
+        self.count += col.len();
+        self.nulls += col.null_count();
+        let fmt = CellFormatter::new(col, &self.settings)?;
+        let mut buf = String::new();
+        for row in 0..col.len() {
+            if col.is_null(row) {
+                continue;
+            }
+            buf.clear();
+            fmt.write(&mut buf, row)?;
+            self.add_value(&buf);
+        }
+        if self.integral {
+            // Every integer type fits in a decimal with 38 digits
+            let col = arrow::compute::cast(col, &DataType::Decimal128(38, 0))?;
+            let col = col.as_primitive::<Decimal128Type>();
+            if let Some(x) = arrow::compute::min(col) {
+                self.min_int = Some(self.min_int.map_or(x, |y| y.min(x)));
+            }
+            if let Some(x) = arrow::compute::max(col) {
+                self.max_int = Some(self.max_int.map_or(x, |y| y.max(x)));
+            }
+        }
+        if self.numeric {
+            let col = arrow::compute::cast(col, &DataType::Float64)?;
+            for x in col.as_primitive::<Float64Type>().iter().flatten() {
+                if !x.is_nan() {
+                    self.add_number(x);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn add_value(&mut self, x: &str) {
+        self.hll.insert(x);
+        if let Some(n) = self.counts.get_mut(x) {
+            *n += 1;
+        } else if self.counts.len() < DISTINCT_LIMIT {
+            self.counts.insert(x.to_owned(), 1);
+        } else {
+            self.overflowed = true;
+        }
+        if !self.numeric {
+            if self.min.as_deref().is_none_or(|min| x < min) {
+                self.min = Some(x.to_owned());
+            }
+            if self.max.as_deref().is_none_or(|max| x > max) {
+                self.max = Some(x.to_owned());
+            }
+        }
+    }
+
+    fn add_number(&mut self, x: f64) {
+        self.n += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.n as f64;
+        self.m2 += delta * (x - self.mean);
+        self.min_num = self.min_num.min(x);
+        self.max_num = self.max_num.max(x);
+        if self.sample.len() < SAMPLE_SIZE {
+            self.sample.push(x);
+        } else {
+            // xorshift64
+            self.rng ^= self.rng << 13;
+            self.rng ^= self.rng >> 7;
+            self.rng ^= self.rng << 17;
+            let idx = (self.rng % self.n as u64) as usize;
+            if idx < SAMPLE_SIZE {
+                self.sample[idx] = x;
+            }
+        }
+    }
+
+    fn fmt_number(&self, x: f64) -> String {
+        if self.integral {
+            format!("{}", x as i128)
+        } else {
+            format!("{x:.dps$}", dps = self.settings.float_dps)
+        }
+    }
+
+    fn summary(&self) -> Summary {
+        let mut top = self
+            .counts
+            .iter()
+            .map(|(x, n)| (x.clone(), *n))
+            .collect::<Vec<_>>();
+        top.sort_unstable_by(|(x1, n1), (x2, n2)| n2.cmp(n1).then_with(|| x1.cmp(x2)));
+        top.truncate(TOP_N);
+
+        let (min, max, mean, stddev, quantiles) = if self.n == 0 {
+            (self.min.clone(), self.max.clone(), None, None, vec![])
+        } else {
+            let mut sample = self.sample.clone();
+            sample.sort_unstable_by(f64::total_cmp);
+            let quantiles = QUANTILES
+                .iter()
+                .map(|q| {
+                    let idx = ((sample.len() - 1) as f64 * q).round() as usize;
+                    self.fmt_number(sample[idx])
+                })
+                .collect();
+            let stddev = (self.n > 1).then(|| (self.m2 / (self.n - 1) as f64).sqrt());
+            let (min, max) = match (self.min_int, self.max_int) {
+                (Some(min), Some(max)) => (min.to_string(), max.to_string()),
+                _ => (self.fmt_number(self.min_num), self.fmt_number(self.max_num)),
+            };
+            (Some(min), Some(max), Some(self.mean), stddev, quantiles)
+        };
+
+        Summary {
+            count: self.count,
+            nulls: self.nulls,
+            distinct: if self.overflowed {
+                self.hll.estimate()
+            } else {
+                self.counts.len()
+            },
+            distinct_is_exact: !self.overflowed,
+            min,
+            max,
+            mean,
+            stddev,
+            quantiles,
+            quantiles_are_exact: self.n <= SAMPLE_SIZE,
+            top,
+            top_is_exact: !self.overflowed,
+        }
+    }
+}
+
+/// Estimates the number of distinct values in a stream
+struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    /// log2 of the number of registers.  The standard error is about
+    /// 1.04 / sqrt(2^P), so 1.6%.
+    const P: u32 = 12;
+
+    fn insert(&mut self, x: &str) {
+        let mut hasher = DefaultHasher::new();
+        x.hash(&mut hasher);
+        let hash = hasher.finish();
+        let idx = (hash >> (64 - Self::P)) as usize;
+        let rank = ((hash << Self::P) | (1 << (Self::P - 1))).leading_zeros() as u8 + 1;
+        self.registers[idx] = self.registers[idx].max(rank);
+    }
+
+    fn estimate(&self) -> usize {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|x| 2f64.powi(-(*x as i32))).sum();
+        let estimate = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|x| **x == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            // Linear counting is more accurate for small cardinalities
+            (m * (m / zeros as f64).ln()).round() as usize
+        } else {
+            estimate.round() as usize
+        }
+    }
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        HyperLogLog {
+            registers: vec![0; 1 << Self::P],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Int64Array, StringArray, UInt64Array};
+    use std::sync::Arc;
+
+    #[test]
+    fn summarises_values() -> anyhow::Result<()> {
+        let settings = RenderSettings::default();
+        let mut acc = Accumulator::new(&DataType::Int64, settings.clone());
+        acc.add(&Int64Array::from(vec![Some(3), None, Some(1), Some(3)]))?;
+        acc.add(&Int64Array::from(vec![Some(5), Some(3)]))?;
+        let summary = acc.summary();
+        assert_eq!((summary.count, summary.nulls, summary.distinct), (6, 1, 3));
+        assert_eq!(summary.min.as_deref(), Some("1"));
+        assert_eq!(summary.max.as_deref(), Some("5"));
+        assert_eq!(summary.mean, Some(3.0));
+        assert_eq!(summary.quantiles[2], "3");
+        assert_eq!(summary.top[0], ("3".to_owned(), 3));
+
+        let mut acc = Accumulator::new(&DataType::Utf8, settings);
+        let values = (0..DISTINCT_LIMIT * 2)
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>();
+        acc.add(&(Arc::new(StringArray::from(values)) as ArrayRef))?;
+        let summary = acc.summary();
+        assert!(!summary.distinct_is_exact);
+        let error = summary.distinct.abs_diff(DISTINCT_LIMIT * 2) as f64;
+        assert!(error / ((DISTINCT_LIMIT * 2) as f64) < 0.05);
+        assert_eq!(summary.min.as_deref(), Some("0"));
+        assert_eq!(summary.mean, None);
+        Ok(())
+    }
+
+    #[test]
+    fn exact_integer_extremes() -> anyhow::Result<()> {
+        let big = (1 << 53) + 1;
+        let mut acc = Accumulator::new(&DataType::Int64, RenderSettings::default());
+        acc.add(&Int64Array::from(vec![big, big + 2]))?;
+        acc.add(&Int64Array::from(vec![big + 4, i64::MIN + 1]))?;
+        let summary = acc.summary();
+        assert_eq!(summary.min, Some((i64::MIN + 1).to_string()));
+        assert_eq!(summary.max, Some((big + 4).to_string()));
+
+        let mut acc = Accumulator::new(&DataType::UInt64, RenderSettings::default());
+        acc.add(&UInt64Array::from(vec![u64::MAX - 1, 0]))?;
+        assert_eq!(acc.summary().max, Some((u64::MAX - 1).to_string()));
+        Ok(())
+    }
+}
@@ -0,0 +1,106 @@
+use crate::sort::{Permutation, Values, ValuesBuilder};
+use arrow::buffer::BooleanBuffer;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Maps the rows of a sorted and/or filtered view onto rows of the source
+pub struct RowMap {
+    perm: Option<Arc<Permutation>>,
+    /// The rows which pass the filters, in ascending order.  These are
+    /// positions in the sorted order if there is one, or source rows if not.
+    kept: Option<Values>,
+}
+
+impl RowMap {
+    /// `mask` says which source rows pass the filters.  Rows beyond the end of
+    /// the mask are excluded.
+    pub fn new(
+        perm: Option<Arc<Permutation>>,
+        mask: Option<&BooleanBuffer>,
+    ) -> anyhow::Result<RowMap> {
+        let kept = match (&perm, mask) {
+            (_, None) => None,
+            (None, Some(mask)) => {
+                let mut kept = ValuesBuilder::default();
+                for row in mask.set_indices() {
+                    kept.push(row as u64)?;
+                }
+                Some(kept.finish()?)
+            }
+            (Some(perm), Some(mask)) => {
+                let mut kept = ValuesBuilder::default();
+                const CHUNK: usize = 65536;
+                for start in (0..perm.len()).step_by(CHUNK) {
+                    let rows = perm.get_range(start..(start + CHUNK).min(perm.len()))?;
+                    for (i, row) in rows.into_iter().enumerate() {
+                        if row < mask.len() && mask.value(row) {
+                            kept.push((start + i) as u64)?;
+                        }
+                    }
+                }
+                Some(kept.finish()?)
+            }
+        };
+        Ok(RowMap { perm, kept })
+    }
+
+    pub fn len(&self) -> usize {
+        match (&self.kept, &self.perm) {
+            (Some(kept), _) => kept.len(),
+            (None, Some(perm)) => perm.len(),
+            (None, None) => 0,
+        }
+    }
+
+    /// The source rows for the given rows of the view
+    pub fn get_range(&self, rows: Range<usize>) -> anyhow::Result<Vec<usize>> {
+        let Some(kept) = &self.kept else {
+            return match &self.perm {
+                Some(perm) => perm.get_range(rows),
+                None => Ok(vec![]),
+            };
+        };
+        let kept = kept.get_range(rows)?;
+        match &self.perm {
+            Some(perm) => kept
+                .into_iter()
+                .map(|x| Ok(perm.get_range(x as usize..x as usize + 1)?[0]))
+                .collect(),
+            None => Ok(kept.into_iter().map(|x| x as usize).collect()),
+        }
+    }
+
+    /// The row of the view at which the given source row appears, if it
+    /// appears at all
+    pub fn position(&self, source_row: usize) -> anyhow::Result<Option<usize>> {
+        let pos = match &self.perm {
+            Some(perm) => perm.position(source_row)?,
+            None => source_row,
+        };
+        match &self.kept {
+            Some(kept) => Ok(kept.binary_search(pos as u64)?.ok()),
+            None => Ok(Some(pos)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{ArrayRef, Int32Array};
+    use arrow::datatypes::DataType;
+
+    #[test]
+    fn sorted_and_filtered() -> anyhow::Result<()> {
+        let keys = Arc::new(Int32Array::from(vec![3, 1, 4, 1, 5])) as ArrayRef;
+        let perm = Permutation::build(std::iter::once(Ok(keys)), &DataType::Int32, false)?;
+        let mask = BooleanBuffer::from(vec![true, false, true, true, false]);
+        let view = RowMap::new(Some(Arc::new(perm)), Some(&mask))?;
+        assert_eq!(view.len(), 3);
+        assert_eq!(view.get_range(0..3)?, [3, 0, 2]);
+        assert_eq!(view.position(0)?, Some(1));
+        assert_eq!(view.position(1)?, None);
+        assert_eq!(view.position(2)?, Some(2));
+        Ok(())
+    }
+}